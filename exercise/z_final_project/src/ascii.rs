@@ -0,0 +1,30 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+// Characters from darkest to brightest, as seen on a dark terminal.
+pub const ASCII_RAMP: &str = " .:-=+*#%@";
+
+// Terminal character cells are roughly twice as tall as they are wide.
+const CELL_ASPECT: f32 = 2.0;
+
+// Renders `img` as `cols` characters per line, one line per text row. The row
+// count keeps the picture's proportions once printed. Transparent pixels are
+// treated as dark, and `invert` flips the ramp for dark text on a light
+// background.
+pub fn to_ascii(img: &DynamicImage, cols: u32, invert: bool) -> String {
+    let rows = (img.height() as f32 * cols as f32 / img.width().max(1) as f32 / CELL_ASPECT).round().max(1.0) as u32;
+    let cells = img.resize_exact(cols, rows, FilterType::Triangle).to_luma_alpha8();
+    let ramp: Vec<char> = if invert { ASCII_RAMP.chars().rev().collect() } else { ASCII_RAMP.chars().collect() };
+    let steps = ramp.len() as u32 - 1;
+
+    let mut out = String::with_capacity(((cols + 1) * rows) as usize);
+    for row in cells.rows() {
+        for pixel in row {
+            let [luma, alpha] = pixel.0;
+            let level = luma as u32 * alpha as u32 / 255;
+            out.push(ramp[((level * steps + 127) / 255) as usize]);
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,70 @@
+use clap::ValueEnum;
+use image::Rgb;
+
+// Palettes for mapping a value between 0.0 and 1.0 to a color.
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum Colormap {
+    // The original look of each generator
+    #[default]
+    Classic,
+    Grayscale,
+    // Black through red and yellow to white
+    Fire,
+    // Black through deep blue and cyan to white
+    Ocean,
+    // Evenly spaced hues from red to magenta
+    Rainbow,
+}
+
+// `Classic` here is the hue ramp used by the escape-time fractals.
+pub fn colormap(t: f32, scheme: Colormap) -> Rgb<u8> {
+    let t = t.clamp(0.0, 1.0);
+    let ramp = |start: f32, rate: f32| ((t * rate - start).clamp(0.0, 1.0) * 255.0).round() as u8;
+    match scheme {
+        Colormap::Classic => hsv_to_rgb(360.0 * (t as f64).sqrt(), 1.0, 1.0),
+        Colormap::Grayscale => Rgb([ramp(0.0, 1.0); 3]),
+        Colormap::Fire => Rgb([ramp(0.0, 3.0), ramp(1.0, 3.0), ramp(2.0, 3.0)]),
+        Colormap::Ocean => Rgb([ramp(2.0, 3.0), ramp(0.5, 1.5), ramp(0.0, 1.5)]),
+        Colormap::Rainbow => hsv_to_rgb(300.0 * t as f64, 1.0, 1.0),
+    }
+}
+
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> Rgb<u8> {
+    let c = value * saturation;
+    let h = (hue.rem_euclid(360.0)) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = value - c;
+    Rgb([
+        ((r + m) * 255.0).round() as u8,
+        ((g + m) * 255.0).round() as u8,
+        ((b + m) * 255.0).round() as u8,
+    ])
+}
+
+pub fn rgb_to_hsv(Rgb([r, g, b]): Rgb<u8>) -> (f64, f64, f64) {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation, max)
+}
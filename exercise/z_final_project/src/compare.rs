@@ -0,0 +1,77 @@
+use std::fmt;
+
+use image::{DynamicImage, Rgb, RgbImage};
+
+// How far apart two images of the same size are. Differences are the largest
+// absolute difference between any of a pixel's RGBA channels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffReport {
+    pub max: u8,
+    pub mean: f64,
+    pub differing: u64,
+    pub pixels: u64,
+}
+
+impl DiffReport {
+    pub fn differing_percent(&self) -> f64 {
+        if self.pixels == 0 {
+            return 0.0;
+        }
+        self.differing as f64 * 100.0 / self.pixels as f64
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "max_difference: {}", self.max)?;
+        writeln!(f, "mean_difference: {:.4}", self.mean)?;
+        writeln!(f, "differing_pixels: {} ({:.2}%)", self.differing, self.differing_percent())
+    }
+}
+
+pub fn compare(a: &DynamicImage, b: &DynamicImage) -> Result<DiffReport, String> {
+    check_dimensions(a, b)?;
+    let (a, b) = (a.to_rgba8(), b.to_rgba8());
+    let mut report = DiffReport { max: 0, mean: 0.0, differing: 0, pixels: a.len() as u64 / 4 };
+    let mut total = 0u64;
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        let diff = pixel_difference(&pa.0, &pb.0);
+        report.max = report.max.max(diff);
+        report.differing += (diff > 0) as u64;
+        total += diff as u64;
+    }
+    if report.pixels > 0 {
+        report.mean = total as f64 / report.pixels as f64;
+    }
+    Ok(report)
+}
+
+// A dimmed grayscale copy of `a` with every pixel that differs from `b` drawn
+// in red, brighter for larger differences.
+pub fn diff_image(a: &DynamicImage, b: &DynamicImage) -> Result<DynamicImage, String> {
+    check_dimensions(a, b)?;
+    let (luma, a, b) = (a.to_luma8(), a.to_rgba8(), b.to_rgba8());
+    Ok(DynamicImage::ImageRgb8(RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        match pixel_difference(&a.get_pixel(x, y).0, &b.get_pixel(x, y).0) {
+            0 => Rgb([luma.get_pixel(x, y)[0] / 3; 3]),
+            diff => Rgb([128 + diff / 2, 0, 0]),
+        }
+    })))
+}
+
+fn check_dimensions(a: &DynamicImage, b: &DynamicImage) -> Result<(), String> {
+    if a.width() != b.width() || a.height() != b.height() {
+        return Err(format!(
+            "Images have different dimensions: {}x{} and {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        ));
+    }
+    Ok(())
+}
+
+fn pixel_difference(a: &[u8; 4], b: &[u8; 4]) -> u8 {
+    a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+use std::io::Read;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_TIMEOUT: u64 = 30;
+pub const DEFAULT_RETRIES: u32 = 2;
+
+static TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT);
+static RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_RETRIES);
+
+#[derive(Debug)]
+pub enum FetchError {
+    // The server answered with a non-2xx status code
+    Status(u16),
+    // The connection failed or timed out, or the body couldn't be read
+    Transport(String),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Status(code) => write!(f, "server responded with HTTP {}", code),
+            FetchError::Transport(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+// Seconds allowed for the whole request, from connecting to the last byte.
+pub fn set_timeout(seconds: u64) {
+    TIMEOUT.store(seconds, Ordering::Relaxed);
+}
+
+// Extra attempts made after a failed connection or a 5xx response.
+pub fn set_retries(retries: u32) {
+    RETRIES.store(retries, Ordering::Relaxed);
+}
+
+pub fn is_url(src: &str) -> bool {
+    src.starts_with("http://") || src.starts_with("https://")
+}
+
+// Downloads the body of `url`. Client errors such as 404 aren't retried since
+// asking again won't change the answer.
+pub fn fetch(url: &str) -> Result<Vec<u8>, FetchError> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(TIMEOUT.load(Ordering::Relaxed)))
+        .build();
+    let mut attempt = 0;
+    loop {
+        let result = get(&agent, url);
+        match result {
+            Err(FetchError::Status(code)) if code < 500 => return result,
+            Err(_) if attempt < RETRIES.load(Ordering::Relaxed) => {
+                attempt += 1;
+                thread::sleep(Duration::from_millis(250 << attempt.min(4)));
+            }
+            _ => return result,
+        }
+    }
+}
+
+fn get(agent: &ureq::Agent, url: &str) -> Result<Vec<u8>, FetchError> {
+    let response = agent.get(url).call().map_err(|err| match err {
+        ureq::Error::Status(code, _) => FetchError::Status(code),
+        // The transport error's own message starts with the URL, which the
+        // caller adds already.
+        ureq::Error::Transport(transport) => {
+            let mut message = transport.kind().to_string();
+            if let Some(source) = std::error::Error::source(&transport) {
+                message = format!("{}: {}", message, source);
+            } else if let Some(detail) = transport.message() {
+                message = format!("{}: {}", message, detail);
+            }
+            FetchError::Transport(message)
+        }
+    })?;
+    if !(200..300).contains(&response.status()) {
+        return Err(FetchError::Status(response.status()));
+    }
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .map_err(|err| FetchError::Transport(err.to_string()))?;
+    Ok(bytes)
+}
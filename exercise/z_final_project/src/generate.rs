@@ -0,0 +1,301 @@
+use clap::ValueEnum;
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+use num_complex::Complex;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+
+use crate::color::{colormap, hsv_to_rgb, Colormap};
+use crate::progress::{is_cancelled, Progress};
+use crate::transform::overlay;
+
+pub fn generate() -> DynamicImage {
+    let width = 800;
+    let height = 800;
+
+    render(width, height, |x, y| {
+        let red = (0.5 * (x as f32 * 0.01).sin() * 255.0) as u8;
+        let green = (0.5 * (y as f32 * 0.01).sin() * 255.0) as u8;
+        let blue = (0.5 * (x as f32 * 0.01 + y as f32 * 0.01).sin() * 255.0) as u8;
+
+        Rgb([red, green, blue])
+    })
+}
+
+// Sums four sine waves with phases derived from `seed`: two along the axes,
+// one diagonal and one radiating from a seeded point. `scale` is roughly the
+// size of a blob in pixels.
+pub fn plasma(scale: f64, seed: u64, width: u32, height: u32) -> DynamicImage {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let phases: [f64; 4] = std::array::from_fn(|_| rng.gen_range(0.0..std::f64::consts::TAU));
+    let center = (rng.gen_range(0.0..width as f64), rng.gen_range(0.0..height as f64));
+
+    render(width, height, |x, y| {
+        let (u, v) = (x as f64 / scale, y as f64 / scale);
+        let (du, dv) = ((x as f64 - center.0) / scale, (y as f64 - center.1) / scale);
+        let value = (u + phases[0]).sin()
+            + (v + phases[1]).sin()
+            + ((u + v) / 2.0 + phases[2]).sin()
+            + ((du * du + dv * dv).sqrt() + phases[3]).sin();
+        hsv_to_rgb(360.0 * (value + 4.0) / 8.0, 1.0, 1.0)
+    })
+}
+
+// A Julia set for the constant cx + cy i. The classic colormap shades the
+// iteration count in green over a red and blue positional gradient.
+pub fn fractal(cx: f32, cy: f32, max_iter: u32, width: u32, height: u32, scheme: Colormap) -> DynamicImage {
+    let scale_x = 3.0 / width as f32;
+    let scale_y = 3.0 / height as f32;
+
+    render(width, height, |x, y| {
+        let red = (0.3 * x as f32) as u8;
+        let blue = (0.3 * y as f32) as u8;
+
+        let zx = y as f32 * scale_x - 1.5;
+        let zy = x as f32 * scale_y - 1.5;
+
+        let c = Complex::new(cx, cy);
+        let mut z = Complex::new(zx, zy);
+
+        let mut i = 0;
+        while i < max_iter && z.norm() <= 2.0 {
+            z = z * z + c;
+            i += 1;
+        }
+        if scheme != Colormap::Classic {
+            if i >= max_iter {
+                return Rgb([0, 0, 0]);
+            }
+            return colormap(i as f32 / max_iter as f32, scheme);
+        }
+        let green = (i as u64 * 255 / max_iter.max(1) as u64) as u8;
+
+        Rgb([red, green, blue])
+    })
+}
+
+// A rows x cols poster of Julia sets, each cell rendered with a constant c
+// taken at evenly spaced angles around the circle |c| = 0.7885 so neighbouring
+// cells morph smoothly from one shape to the next.
+pub fn fractal_grid(rows: u32, cols: u32, cell_width: u32, cell_height: u32, scheme: Colormap) -> DynamicImage {
+    let cells = rows * cols;
+    let base = DynamicImage::ImageRgb8(ImageBuffer::new(cols * cell_width, rows * cell_height));
+    (0..cells).fold(base, |grid, i| {
+        let c = Complex::from_polar(0.7885, std::f32::consts::TAU * i as f32 / cells.max(1) as f32);
+        let cell = fractal(c.re, c.im, 255, cell_width, cell_height, scheme);
+        let (x, y) = ((i % cols * cell_width) as i64, (i / cols * cell_height) as i64);
+        overlay(grid, cell, x, y, 1.0)
+    })
+}
+
+pub fn mandelbrot(
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    center: (f64, f64),
+    zoom: f64,
+    scheme: Colormap,
+) -> DynamicImage {
+    escape_time(width, height, max_iter, center, zoom, scheme, |z, c| z * z + c)
+}
+
+pub fn burning_ship(
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    center: (f64, f64),
+    zoom: f64,
+    scheme: Colormap,
+) -> DynamicImage {
+    escape_time(width, height, max_iter, center, zoom, scheme, |z, c| {
+        let z = Complex::new(z.re.abs(), z.im.abs());
+        z * z + c
+    })
+}
+
+// Renders an escape-time fractal where each pixel is the starting point `c` and
+// `step` is iterated from z = 0 until |z| > 2 or `max_iter` is reached.
+fn escape_time<F>(
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    center: (f64, f64),
+    zoom: f64,
+    scheme: Colormap,
+    step: F,
+) -> DynamicImage
+where
+    F: Fn(Complex<f64>, Complex<f64>) -> Complex<f64> + Sync,
+{
+    let scale = 3.0 / (zoom * width.min(height) as f64);
+
+    render(width, height, |x, y| {
+        let cx = center.0 + (x as f64 - width as f64 / 2.0) * scale;
+        let cy = center.1 + (y as f64 - height as f64 / 2.0) * scale;
+
+        let c = Complex::new(cx, cy);
+        let mut z = Complex::new(0.0, 0.0);
+
+        let mut i = 0;
+        while i < max_iter && z.norm_sqr() <= 4.0 {
+            z = step(z, c);
+            i += 1;
+        }
+
+        smooth_color(i, max_iter, z.norm(), scheme)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum NoiseKind {
+    White,
+    Perlin,
+}
+
+// The same seed always produces the same image, so noise can be used as a
+// regression fixture.
+pub fn noise(kind: NoiseKind, seed: u64, width: u32, height: u32) -> DynamicImage {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    match kind {
+        NoiseKind::White => {
+            let mut buf = vec![0u8; width as usize * height as usize * 3];
+            rng.fill(buf.as_mut_slice());
+            DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, buf).unwrap())
+        }
+        NoiseKind::Perlin => {
+            let octaves: Vec<ValueNoise> = (0..4).map(|octave| ValueNoise::new(&mut rng, 64 >> octave)).collect();
+            render(width, height, |x, y| {
+                let mut value = 0.0;
+                let mut amplitude = 0.5;
+                for octave in &octaves {
+                    value += amplitude * octave.sample(x, y);
+                    amplitude /= 2.0;
+                }
+                let gray = (value / 0.9375 * 255.0).round() as u8;
+                Rgb([gray, gray, gray])
+            })
+        }
+    }
+}
+
+// A lattice of random values one `cell` apart, smoothly interpolated between
+// the lattice points.
+struct ValueNoise {
+    cell: u32,
+    lattice: [[f32; 64]; 64],
+}
+
+impl ValueNoise {
+    fn new(rng: &mut ChaCha8Rng, cell: u32) -> Self {
+        let mut lattice = [[0.0; 64]; 64];
+        for row in lattice.iter_mut() {
+            rng.fill(row);
+        }
+        ValueNoise { cell, lattice }
+    }
+
+    fn sample(&self, x: u32, y: u32) -> f32 {
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let at = |ix: u32, iy: u32| self.lattice[iy as usize % 64][ix as usize % 64];
+
+        let (ix, iy) = (x / self.cell, y / self.cell);
+        let tx = smooth((x % self.cell) as f32 / self.cell as f32);
+        let ty = smooth((y % self.cell) as f32 / self.cell as f32);
+
+        let top = at(ix, iy) + (at(ix + 1, iy) - at(ix, iy)) * tx;
+        let bottom = at(ix, iy + 1) + (at(ix + 1, iy + 1) - at(ix, iy + 1)) * tx;
+        top + (bottom - top) * ty
+    }
+}
+
+// Renders `frames` Mandelbrot images whose zoom moves from `zoom.0` to `zoom.1`.
+// The zoom is interpolated geometrically so the animation zooms in at a steady pace.
+// Once cancelled no further frames are rendered.
+pub fn mandelbrot_zoom(
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    center: (f64, f64),
+    zoom: (f64, f64),
+    frames: u32,
+    scheme: Colormap,
+) -> Vec<DynamicImage> {
+    let (start, end) = zoom;
+    (0..frames)
+        .take_while(|_| !is_cancelled())
+        .map(|frame| {
+            let t = if frames > 1 { frame as f64 / (frames - 1) as f64 } else { 0.0 };
+            mandelbrot(width, height, max_iter, center, start * (end / start).powf(t), scheme)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+    Diagonal,
+}
+
+pub fn gradient(from: [u8; 3], to: [u8; 3], width: u32, height: u32, direction: Direction) -> DynamicImage {
+    let fraction = |pos: u32, len: u32| if len > 1 { pos as f32 / (len - 1) as f32 } else { 0.0 };
+
+    render(width, height, |x, y| {
+        let t = match direction {
+            Direction::Horizontal => fraction(x, width),
+            Direction::Vertical => fraction(y, height),
+            Direction::Diagonal => (fraction(x, width) + fraction(y, height)) / 2.0,
+        };
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Rgb([mix(from[0], to[0]), mix(from[1], to[1]), mix(from[2], to[2])])
+    })
+}
+
+pub fn solid(color: [u8; 4], width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(ImageBuffer::from_pixel(width, height, Rgba(color)))
+}
+
+pub fn checkerboard(cell_size: u32, width: u32, height: u32, color1: [u8; 3], color2: [u8; 3]) -> DynamicImage {
+    let cell_size = cell_size.max(1);
+    render(width, height, |x, y| {
+        if (x / cell_size + y / cell_size).is_multiple_of(2) {
+            Rgb(color1)
+        } else {
+            Rgb(color2)
+        }
+    })
+}
+
+// Computes every pixel with `pixel(x, y)`, rendering rows in parallel straight
+// into the raw RGB buffer. Once cancelled the remaining rows are left black.
+fn render<F>(width: u32, height: u32, pixel: F) -> DynamicImage
+where
+    F: Fn(u32, u32) -> Rgb<u8> + Sync,
+{
+    let row_len = width as usize * 3;
+    let mut buf = vec![0u8; row_len * height as usize];
+    let progress = Progress::new("Rendering", height as usize);
+
+    buf.par_chunks_mut(row_len.max(1)).enumerate().for_each(|(y, row)| {
+        if is_cancelled() {
+            return;
+        }
+        for (x, rgb) in row.chunks_exact_mut(3).enumerate() {
+            rgb.copy_from_slice(&pixel(x as u32, y as u32).0);
+        }
+        progress.inc();
+    });
+    progress.finish();
+
+    DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, buf).unwrap())
+}
+
+// Colors an escape-time result using the fractional iteration count, so the
+// bands between iterations blend into a continuous gradient.
+fn smooth_color(iterations: u32, max_iter: u32, norm: f64, scheme: Colormap) -> Rgb<u8> {
+    if iterations >= max_iter {
+        return Rgb([0, 0, 0]);
+    }
+    let smooth = iterations as f64 + 1.0 - norm.ln().ln() / std::f64::consts::LN_2;
+    colormap((smooth / max_iter as f64) as f32, scheme)
+}
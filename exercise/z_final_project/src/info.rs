@@ -0,0 +1,262 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: String,
+    pub pixels: u64,
+    pub bytes: usize,
+    pub format: Option<String>,
+}
+
+impl ImageInfo {
+    // `format` is the format the image was decoded from, when it is known.
+    pub fn new(img: &DynamicImage, format: Option<ImageFormat>) -> ImageInfo {
+        ImageInfo {
+            width: img.width(),
+            height: img.height(),
+            color_type: format!("{:?}", img.color()),
+            pixels: img.width() as u64 * img.height() as u64,
+            bytes: img.as_bytes().len(),
+            format: format.map(|format| format!("{:?}", format)),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("image info is always serializable")
+    }
+}
+
+impl fmt::Display for ImageInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "width: {}", self.width)?;
+        writeln!(f, "height: {}", self.height)?;
+        writeln!(f, "color_type: {}", self.color_type)?;
+        writeln!(f, "pixels: {}", self.pixels)?;
+        writeln!(f, "bytes: {} ({:.1} MiB)", self.bytes, self.bytes as f64 / (1024.0 * 1024.0))?;
+        writeln!(f, "format: {}", self.format.as_deref().unwrap_or("unknown"))
+    }
+}
+
+// Summary of one channel on the 0-255 scale.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ChannelSummary {
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelStats {
+    pub r: ChannelSummary,
+    pub g: ChannelSummary,
+    pub b: ChannelSummary,
+    pub a: ChannelSummary,
+    // Pixels with an alpha of 0 and 255.
+    pub transparent: u64,
+    pub opaque: u64,
+}
+
+impl ChannelStats {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("channel stats are always serializable")
+    }
+}
+
+impl fmt::Display for ChannelStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "channel  min  max      mean    stddev")?;
+        for (name, channel) in [("r", &self.r), ("g", &self.g), ("b", &self.b), ("a", &self.a)] {
+            let ChannelSummary { min, max, mean, stddev } = channel;
+            writeln!(f, "{:<7} {:>4} {:>4} {:>9.4} {:>9.4}", name, min, max, mean, stddev)?;
+        }
+        writeln!(f, "transparent: {}", self.transparent)?;
+        writeln!(f, "opaque: {}", self.opaque)
+    }
+}
+
+// Per-channel min, max, mean and population standard deviation. Images
+// without alpha count as fully opaque. Sums are kept in f64 so large images
+// don't overflow.
+pub fn stats(img: &DynamicImage) -> ChannelStats {
+    let buf = img.to_rgba8();
+    let mut min = [u8::MAX; 4];
+    let mut max = [u8::MIN; 4];
+    let mut sum = [0f64; 4];
+    let mut squares = [0f64; 4];
+    let (mut transparent, mut opaque) = (0, 0);
+    for pixel in buf.pixels() {
+        for (i, &value) in pixel.0.iter().enumerate() {
+            min[i] = min[i].min(value);
+            max[i] = max[i].max(value);
+            sum[i] += value as f64;
+            squares[i] += value as f64 * value as f64;
+        }
+        match pixel[3] {
+            0 => transparent += 1,
+            255 => opaque += 1,
+            _ => {}
+        }
+    }
+
+    let count = (buf.width() as f64 * buf.height() as f64).max(1.0);
+    let [r, g, b, a] = std::array::from_fn(|i| {
+        let mean = sum[i] / count;
+        ChannelSummary {
+            min: min[i],
+            max: max[i],
+            mean,
+            stddev: (squares[i] / count - mean * mean).max(0.0).sqrt(),
+        }
+    });
+    ChannelStats { r, g, b, a, transparent, opaque }
+}
+
+// Describes an image as `key: value` lines.
+pub fn image_info(img: &DynamicImage, format: Option<ImageFormat>) -> String {
+    ImageInfo::new(img, format).to_string()
+}
+
+// SHA-256 of the raw pixel data as lowercase hex. Samples wider than a byte are
+// hashed little-endian so the result doesn't depend on the platform.
+pub fn hash_image(img: &DynamicImage) -> String {
+    let mut hasher = Sha256::new();
+    match img {
+        DynamicImage::ImageLuma16(buf) => buf.iter().for_each(|v| hasher.update(v.to_le_bytes())),
+        DynamicImage::ImageLumaA16(buf) => buf.iter().for_each(|v| hasher.update(v.to_le_bytes())),
+        DynamicImage::ImageRgb16(buf) => buf.iter().for_each(|v| hasher.update(v.to_le_bytes())),
+        DynamicImage::ImageRgba16(buf) => buf.iter().for_each(|v| hasher.update(v.to_le_bytes())),
+        DynamicImage::ImageRgb32F(buf) => buf.iter().for_each(|v| hasher.update(v.to_le_bytes())),
+        DynamicImage::ImageRgba32F(buf) => buf.iter().for_each(|v| hasher.update(v.to_le_bytes())),
+        img => hasher.update(img.as_bytes()),
+    }
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The number of pixels at each value of the red, green and blue channels.
+pub fn histogram(img: &DynamicImage) -> [[u32; 256]; 3] {
+    let mut hist = [[0; 256]; 3];
+    for pixel in img.to_rgb8().pixels() {
+        for (bins, &value) in hist.iter_mut().zip(&pixel.0) {
+            bins[value as usize] += 1;
+        }
+    }
+    hist
+}
+
+// One `bin,r,g,b` row per value, after a header row.
+pub fn histogram_csv(hist: &[[u32; 256]; 3]) -> String {
+    let mut csv = String::from("bin,r,g,b\n");
+    for (bin, ((r, g), b)) in hist[0].iter().zip(&hist[1]).zip(&hist[2]).enumerate() {
+        csv += &format!("{},{},{},{}\n", bin, r, g, b);
+    }
+    csv
+}
+
+// Draws the histogram as one column per bin on a black background. Bars are
+// scaled so the tallest bin reaches the top, and the channels are added
+// together so overlapping bars show up as mixed colors.
+pub fn histogram_chart(hist: &[[u32; 256]; 3], height: u32) -> DynamicImage {
+    let tallest = hist.iter().flatten().copied().max().unwrap_or(0).max(1) as u64;
+    DynamicImage::ImageRgb8(RgbImage::from_fn(256, height, |x, y| {
+        let level = (height - y) as u64;
+        let mut pixel = [0; 3];
+        for (channel, bins) in pixel.iter_mut().zip(hist) {
+            let bar = (bins[x as usize] as u64 * height as u64).div_ceil(tallest);
+            if bar >= level {
+                *channel = 255;
+            }
+        }
+        Rgb(pixel)
+    }))
+}
+
+// Images are shrunk to at most this many pixels across before clustering.
+const PALETTE_SAMPLE_SIZE: u32 = 128;
+const PALETTE_ITERATIONS: usize = 20;
+
+// The `n` most common colors found by k-means clustering, most common first.
+// Starting centers are picked farthest-first so the result is the same on
+// every run, and fewer colors come back when the image has fewer distinct
+// ones. Fully transparent pixels are left out.
+pub fn dominant_colors(img: &DynamicImage, n: usize) -> Vec<Rgb<u8>> {
+    let sample = if img.width().max(img.height()) > PALETTE_SAMPLE_SIZE {
+        img.thumbnail(PALETTE_SAMPLE_SIZE, PALETTE_SAMPLE_SIZE).to_rgba8()
+    } else {
+        img.to_rgba8()
+    };
+    let pixels: Vec<[f64; 3]> = sample
+        .pixels()
+        .filter(|pixel| pixel[3] > 0)
+        .map(|pixel| [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64])
+        .collect();
+    if pixels.is_empty() || n == 0 {
+        return Vec::new();
+    }
+    let distance = |a: &[f64; 3], b: &[f64; 3]| a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f64>();
+    let nearest = |centers: &[[f64; 3]], pixel: &[f64; 3]| {
+        (0..centers.len())
+            .min_by(|&i, &j| distance(&centers[i], pixel).total_cmp(&distance(&centers[j], pixel)))
+            .unwrap_or(0)
+    };
+
+    let mean = pixels.iter().fold([0.0; 3], |sum, p| [sum[0] + p[0], sum[1] + p[1], sum[2] + p[2]]);
+    let mut centers = vec![mean.map(|total| total / pixels.len() as f64)];
+    while centers.len() < n {
+        let (farthest, gap) = pixels
+            .iter()
+            .map(|pixel| (pixel, distance(&centers[nearest(&centers, pixel)], pixel)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("pixels is not empty");
+        if gap == 0.0 {
+            break;
+        }
+        centers.push(*farthest);
+    }
+
+    let mut counts = vec![0usize; centers.len()];
+    for _ in 0..PALETTE_ITERATIONS {
+        let mut sums = vec![[0.0; 3]; centers.len()];
+        counts.iter_mut().for_each(|count| *count = 0);
+        for pixel in &pixels {
+            let cluster = nearest(&centers, pixel);
+            counts[cluster] += 1;
+            (0..3).for_each(|c| sums[cluster][c] += pixel[c]);
+        }
+        let mut moved = false;
+        for ((center, sum), &count) in centers.iter_mut().zip(&sums).zip(&counts) {
+            if count > 0 {
+                let updated = sum.map(|total| total / count as f64);
+                moved |= updated != *center;
+                *center = updated;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    let mut clusters: Vec<(usize, [f64; 3])> = counts.into_iter().zip(centers).filter(|(count, _)| *count > 0).collect();
+    clusters.sort_by_key(|&(count, _)| Reverse(count));
+    clusters.into_iter().map(|(_, center)| Rgb(center.map(|c| c.round() as u8))).collect()
+}
+
+// `#rrggbb` in lowercase.
+pub fn hex_color(Rgb([r, g, b]): Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// One `size` pixel square per color, side by side.
+pub fn palette_swatch(colors: &[Rgb<u8>], size: u32) -> DynamicImage {
+    let width = size * colors.len().max(1) as u32;
+    DynamicImage::ImageRgb8(RgbImage::from_fn(width, size, |x, _| {
+        colors.get((x / size) as usize).copied().unwrap_or(Rgb([0, 0, 0]))
+    }))
+}
@@ -0,0 +1,16 @@
+pub mod ascii;
+pub mod color;
+pub mod compare;
+pub mod fetch;
+pub mod generate;
+pub mod info;
+pub mod log;
+pub mod lut;
+pub mod parse;
+pub mod pipeline;
+pub mod preset;
+pub mod progress;
+pub mod stream;
+pub mod text;
+pub mod timing;
+pub mod transform;
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum LogFormat {
+    // Plain messages, meant to be read by people
+    #[default]
+    Text,
+    // One JSON object per line, meant to be parsed by scripts
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    level: Level,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<&'a str>,
+    message: &'a str,
+}
+
+// Drops everything but errors when set.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_format(format: LogFormat) {
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+pub fn format() -> LogFormat {
+    if JSON.load(Ordering::Relaxed) {
+        LogFormat::Json
+    } else {
+        LogFormat::Text
+    }
+}
+
+// Formats a single log line without the trailing newline.
+pub fn render(level: Level, kind: Option<&str>, message: &str, format: LogFormat) -> String {
+    match format {
+        LogFormat::Json => {
+            serde_json::to_string(&Record { level, kind, message }).expect("log records are always serializable")
+        }
+        LogFormat::Text => match level {
+            Level::Info => message.to_string(),
+            Level::Warning => format!("Warning: {}", message),
+            Level::Error => format!("Error: {}", message),
+        },
+    }
+}
+
+// Status messages such as per-file progress.
+pub fn info(message: &str) {
+    if !is_quiet() {
+        eprintln!("{}", render(Level::Info, None, message, format()));
+    }
+}
+
+pub fn warn(message: &str) {
+    if !is_quiet() {
+        eprintln!("{}", render(Level::Warning, None, message, format()));
+    }
+}
+
+// Errors are always written, `kind` names the type of failure for scripts.
+pub fn error(kind: &str, message: &str) {
+    eprintln!("{}", render(Level::Error, Some(kind), message, format()));
+}
@@ -0,0 +1,45 @@
+use std::fs;
+
+// A 1D color lookup table read from a file with three lines of 256
+// comma-separated values, mapping each red, green and blue value in turn.
+// Blank lines and lines starting with '#' are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    pub path: String,
+    pub table: [[u8; 256]; 3],
+}
+
+pub fn parse_lut(s: &str) -> Result<[[u8; 256]; 3], String> {
+    let mut table = [[0; 256]; 3];
+    let mut rows = 0;
+    for (number, line) in (1..).zip(s.lines()) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let row = table
+            .get_mut(rows)
+            .ok_or_else(|| format!("line {}: expected only 3 lines of values (R, G and B)", number))?;
+        let values: Vec<&str> = line.split(',').collect();
+        if values.len() != 256 {
+            return Err(format!("line {}: expected 256 values, found {}", number, values.len()));
+        }
+        for (entry, value) in row.iter_mut().zip(values) {
+            *entry = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("line {}: invalid value {:?} (must be 0-255)", number, value.trim()))?;
+        }
+        rows += 1;
+    }
+    if rows != 3 {
+        return Err(format!("expected 3 lines of values (R, G and B), found {}", rows));
+    }
+    Ok(table)
+}
+
+pub fn load_lut(path: &str) -> Result<Lut, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("Failed to read LUT {}: {}", path, err))?;
+    let table = parse_lut(&contents).map_err(|err| format!("Invalid LUT {}: {}", path, err))?;
+    Ok(Lut { path: path.to_string(), table })
+}
@@ -1,206 +1,1953 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::process::exit;
-use clap::{CommandFactory, Parser, Subcommand};
-use image::DynamicImage;
+use std::time::Instant;
+use clap::parser::ValueSource;
+use clap::{
+    ArgGroup, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use clap_complete::Shell;
+use clap_mangen::Man;
+#[cfg(feature = "avif")]
+use image::codecs::avif::AvifEncoder;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+#[cfg(feature = "webp-lossy")]
+use image::codecs::webp::WebPQuality;
+use image::io::Reader as ImageReader;
+#[cfg(feature = "avif")]
+use image::ImageEncoder;
+use image::{ColorType, Delay, DynamicImage, Frame, ImageError, ImageFormat, ImageResult};
+use mirage::parse::{
+    parse_aspect, parse_bloom, parse_border, parse_channel_order, parse_colorkey, parse_crop,
+    parse_crop_pct, parse_dimensions, parse_dog, parse_fraction, parse_geometry, parse_keep_hue,
+    parse_kernel, parse_levels, parse_manifest, parse_oil, parse_fuzz, parse_opacity, parse_pad,
+    parse_percent, parse_point, parse_positive, parse_rgb, parse_rgba, parse_sharpen,
+    parse_temperature,
+};
+use mirage::info::{
+    dominant_colors, hash_image, hex_color, histogram, histogram_chart, histogram_csv,
+    palette_swatch, stats, ImageInfo,
+};
+use mirage::log::{self, LogFormat};
+use mirage::lut::{load_lut, Lut};
+use mirage::pipeline::{apply_pipeline, apply_pipeline_in_region, Operation};
+use mirage::ascii::to_ascii;
+use mirage::color::Colormap;
+use mirage::compare::{compare, diff_image};
+use mirage::fetch::{self, fetch, is_url, FetchError, DEFAULT_RETRIES, DEFAULT_TIMEOUT};
+use mirage::generate::{
+    burning_ship, checkerboard, fractal, fractal_grid, generate, gradient, mandelbrot,
+    mandelbrot_zoom, noise, plasma, solid, Direction, NoiseKind,
+};
+use mirage::preset::{load_preset, Preset};
+use mirage::progress;
+use mirage::timing::{self, format_timing};
+use mirage::stream::stream_png;
+use mirage::text::{default_font, draw_text, load_font};
+use mirage::transform::{
+    flatten, montage, orient, overlay, split, tile, watermark_tiled, Channel, ColorTarget, Dither,
+    PadMode, DESKEW_MAX_ANGLE,
+};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+// Help heading of the options that build the operation list. Only the
+// commands that apply the operations accept them.
+const TRANSFORM_HEADING: &str = "Transform options";
+
+// Side of each color square written by `palette --swatch`.
+const SWATCH_SIZE: u32 = 64;
 
 #[derive(Parser)]
 #[command(name = "ImageProcessor")]
 #[command(about = "A command line tool to process images", long_about = None)]
 struct Cli {
+    #[arg(long, help = "Rotate and flip input images upright according to their EXIF orientation")]
+    auto_orient: bool,
+    #[arg(
+        long,
+        conflicts_with = "auto_orient",
+        help = "Transform PNGs a few rows at a time to limit memory use; only supports per-pixel \
+                operations such as brighten, invert or threshold, and names any other operation it can't stream"
+    )]
+    stream: bool,
+    #[arg(short, long, help = "Report progress of long running operations on stderr")]
+    verbose: bool,
+    #[arg(short, long, conflicts_with = "verbose", help = "Only print errors and the output that was asked for")]
+    quiet: bool,
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, help = "Format of warnings and errors on stderr")]
+    log_format: LogFormat,
+    #[arg(long, help = "Print the operations that would be applied and exit without processing")]
+    dry_run: bool,
+    #[arg(long, value_enum, help = "Output format, required when OUTFILE is '-' (stdout)")]
+    format: Option<OutputFormat>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        help = "JPEG, WebP and AVIF quality, ignored for other formats"
+    )]
+    quality: Option<u8>,
+    #[arg(
+        long,
+        conflicts_with = "quality",
+        help = "Write lossless WebP, the default if lossy WebP isn't compiled in; ignored for other formats"
+    )]
+    lossless: bool,
+    #[arg(
+        long,
+        conflicts_with = "stream",
+        help = "Print a SHA-256 of the final pixels to stderr, stable across runs and platforms"
+    )]
+    print_hash: bool,
+    #[arg(
+        long,
+        value_parser = parse_rgb,
+        value_name = "R,G,B",
+        help = "Composite transparent output over this color when the output format can't store alpha"
+    )]
+    flatten: Option<[u8; 3]>,
+    #[arg(long, help = "Fail instead of warning when transform options are passed to a command that ignores them")]
+    strict: bool,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = DEFAULT_TIMEOUT,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Give up on an http(s) INFILE that hasn't fully downloaded after SECONDS"
+    )]
+    timeout: u64,
+    #[arg(long, default_value_t = DEFAULT_RETRIES, help = "How many more times to try an http(s) INFILE after a network or server error")]
+    retries: u32,
+    #[arg(
+        long,
+        conflicts_with = "quiet",
+        help = "Print how long loading, saving and each operation take, and the total, on stderr"
+    )]
+    timings: bool,
+    #[arg(long, help = "List the image formats supported by this build and exit")]
+    list_formats: bool,
+    #[command(flatten)]
+    transforms: TransformArgs,
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = TRANSFORM_HEADING)]
+struct TransformArgs {
     #[arg(short = 'u', long, help = "")]
     blur: Option<f32>,
+    #[arg(long, value_name = "RADIUS", help = "Approximate --blur with repeated box blurs, much faster for large radii")]
+    fast_blur: Option<u32>,
+    #[arg(long, value_name = "RADIUS", help = "Median filter, removes speckle noise while keeping edges sharp")]
+    median: Option<u32>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        value_name = "BLOCK",
+        help = "Replace each BLOCK x BLOCK cell with its average color"
+    )]
+    pixelate: Option<u32>,
+    #[arg(
+        long,
+        value_parser = parse_crop,
+        value_name = "X,Y,W,H",
+        requires = "pixelate",
+        help = "Only pixelate this region, leaving the rest of the image sharp"
+    )]
+    pixelate_region: Option<(u32, u32, u32, u32)>,
+    #[arg(long, value_parser = parse_sharpen, value_name = "SIGMA,THRESHOLD")]
+    sharpen: Option<(f32, i32)>,
     #[arg(short, long)]
     brighten: Option<i32>,
+    #[arg(
+        long,
+        value_parser = parse_kernel,
+        allow_hyphen_values = true,
+        value_name = "a,b,c;d,e,f;g,h,i",
+        help = "Convolve with a 3x3 kernel, applied to each channel independently and normalized by its sum"
+    )]
+    convolve: Option<[f32; 9]>,
+    #[arg(long)]
+    emboss: bool,
+    #[arg(
+        long,
+        value_parser = parse_levels,
+        allow_hyphen_values = true,
+        value_name = "R,G,B",
+        help = "Add a delta to each color channel"
+    )]
+    levels: Option<(i32, i32, i32)>,
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Adjust contrast, positive increases and negative reduces (sensible range -100.0 to 100.0)"
+    )]
+    contrast: Option<f32>,
+    #[arg(long, help = "Stretch the luminance range to span 0-255")]
+    auto_contrast: bool,
+    #[arg(
+        long,
+        value_parser = parse_percent,
+        value_name = "PERCENT",
+        default_value_t = 0.0,
+        requires = "auto_contrast",
+        help = "Ignore the darkest and brightest PERCENT of pixels when finding the range for --auto-contrast"
+    )]
+    clip: f32,
+    #[arg(
+        long,
+        value_parser = parse_positive,
+        help = "Gamma correction, values above 1.0 brighten the midtones and below 1.0 darken them"
+    )]
+    gamma: Option<f64>,
+    #[arg(
+        long,
+        value_parser = load_lut,
+        value_name = "FILE",
+        help = "Remap colors with a 1D lookup table, FILE has three lines of 256 comma-separated values for R, G and B"
+    )]
+    lut: Option<Lut>,
+    #[arg(long, allow_hyphen_values = true, value_name = "DEGREES")]
+    huerotate: Option<i32>,
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        value_parser = parse_temperature,
+        value_name = "OFFSET",
+        help = "Shift the white balance, -100 is coolest and 100 warmest"
+    )]
+    temperature: Option<i32>,
+    #[arg(
+        long,
+        value_parser = parse_keep_hue,
+        value_name = "HUE,TOLERANCE",
+        help = "Turn everything gray except colors within TOLERANCE degrees of HUE"
+    )]
+    keep_hue: Option<(f32, f32)>,
+    #[arg(long, help = "Scale color saturation, 0.0 removes all color and 1.0 leaves it unchanged")]
+    saturate: Option<f32>,
+    #[arg(long, value_parser = parse_dimensions, value_name = "WIDTHxHEIGHT")]
+    resize: Option<(u32, u32)>,
+    #[arg(long, requires = "resize")]
+    keep_aspect: bool,
+    #[arg(
+        long,
+        value_parser = parse_dimensions,
+        value_name = "WIDTHxHEIGHT",
+        help = "Shrink to fit within the box keeping the aspect ratio, smaller images are left unchanged"
+    )]
+    thumbnail: Option<(u32, u32)>,
+    #[arg(
+        long,
+        value_parser = parse_dimensions,
+        value_name = "WIDTHxHEIGHT",
+        help = "Resize with nearest-neighbor sampling, keeps pixel art crisp"
+    )]
+    pixel_resize: Option<(u32, u32)>,
+    #[arg(
+        long,
+        value_parser = parse_dimensions,
+        value_name = "WIDTHxHEIGHT",
+        help = "Shrink by removing the least noticeable seams of pixels, keeping important content undistorted; \
+                slow on large images and carves at most half of each dimension"
+    )]
+    seam_carve: Option<(u32, u32)>,
+    #[arg(
+        long,
+        value_parser = parse_positive,
+        value_name = "FACTOR",
+        help = "Scale both dimensions, e.g. 0.5 for half size"
+    )]
+    scale: Option<f64>,
     #[arg(short, long, value_parser = parse_crop)]
     crop: Option<(u32, u32, u32, u32)>,
+    #[arg(
+        long,
+        value_parser = parse_geometry,
+        value_name = "WxH+X+Y",
+        conflicts_with = "crop",
+        help = "Crop using ImageMagick style geometry, the +X+Y offset defaults to +0+0"
+    )]
+    geometry: Option<(u32, u32, u32, u32)>,
+    #[arg(long, value_parser = parse_crop_pct, value_name = "X%,Y%,W%,H%")]
+    crop_pct: Option<(f32, f32, f32, f32)>,
+    #[arg(long, value_parser = parse_border, value_name = "WIDTH,R,G,B", help = "Add a WIDTH pixel frame around the image")]
+    border: Option<(u32, [u8; 3])>,
+    #[arg(long, help = "Crop away a uniform border, by default the color of the top-left pixel")]
+    trim: bool,
+    #[arg(long, value_parser = parse_rgb, value_name = "R,G,B", requires = "trim", help = "Border color for --trim")]
+    trim_color: Option<[u8; 3]>,
+    #[arg(
+        long,
+        value_parser = parse_fuzz,
+        value_name = "TOLERANCE",
+        default_value_t = 0.0,
+        requires = "trim",
+        help = "How far from the border color a pixel may be and still be trimmed"
+    )]
+    fuzz: f32,
+    #[arg(
+        long,
+        value_parser = parse_pad,
+        value_name = "N,MODE",
+        help = "Grow the canvas by N pixels on each side before filtering; MODE is mirror, edge or \
+                constant (optionally followed by R,G,B, black by default)"
+    )]
+    pad: Option<(u32, PadMode, [u8; 3])>,
+    #[arg(
+        long,
+        value_parser = parse_aspect,
+        value_name = "W:H",
+        help = "Crop to the largest centered rectangle with this aspect ratio, e.g. 16:9"
+    )]
+    crop_aspect: Option<(u32, u32)>,
     #[arg(short, long)]
     rotate: Option<i32>,
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        value_name = "DEGREES",
+        help = "Rotate clockwise by any angle"
+    )]
+    rotate_deg: Option<f32>,
+    #[arg(
+        long,
+        value_parser = parse_rgba,
+        value_name = "R,G,B,A",
+        default_value = "0,0,0,0",
+        requires = "rotate_deg",
+        help = "Color of the corners exposed by --rotate-deg"
+    )]
+    fill: [u8; 4],
+    #[arg(long, requires = "rotate_deg", help = "Grow the canvas to fit the image rotated by --rotate-deg")]
+    expand: bool,
+    #[arg(long, help = "Straighten scans by rotating the dominant lines level, corners are filled with white")]
+    deskew: bool,
+    #[arg(
+        long,
+        value_parser = parse_positive,
+        value_name = "DEGREES",
+        default_value_t = DESKEW_MAX_ANGLE as f64,
+        requires = "deskew",
+        help = "Largest skew --deskew looks for in either direction"
+    )]
+    deskew_max_angle: f64,
+    #[arg(long)]
+    fliph: bool,
+    #[arg(long)]
+    flipv: bool,
     #[arg(short, long)]
     invert: bool,
     #[arg(short, long)]
     grayscale: bool,
-    #[command(subcommand)]
-    command: Commands,
+    #[arg(long)]
+    sepia: bool,
+    #[arg(
+        long,
+        value_parser = parse_channel_order,
+        value_name = "ORDER",
+        help = "Reorder the color channels, ORDER is a permutation of rgb such as bgr"
+    )]
+    swap_channels: Option<[usize; 3]>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CHANNEL",
+        help = "Replace the image with a grayscale image of one channel"
+    )]
+    extract_channel: Option<Channel>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u16).range(2..=256),
+        value_name = "LEVELS",
+        help = "Snap each channel to LEVELS evenly spaced values"
+    )]
+    posterize: Option<u16>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u16).range(1..=256),
+        value_name = "COLORS",
+        help = "Reduce the image to at most COLORS colors chosen by median cut"
+    )]
+    palette: Option<u16>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Dither::None,
+        help = "How --posterize and --palette hide banding in smooth gradients"
+    )]
+    dither: Dither,
+    #[arg(long, conflicts_with = "grayscale", help = "Binarize, pixels with luma >= VALUE become white")]
+    threshold: Option<u8>,
+    #[arg(
+        long,
+        conflicts_with_all = ["grayscale", "sepia", "threshold", "saturate", "huerotate", "temperature", "posterize", "levels"],
+        help = "Replace the image with a grayscale Sobel edge map"
+    )]
+    edges: bool,
+    #[arg(
+        long,
+        value_parser = parse_dog,
+        value_name = "SIGMA1,SIGMA2",
+        conflicts_with = "edges",
+        help = "Replace the image with the difference of two Gaussian blurs of its luma, a line art effect"
+    )]
+    dog: Option<(f32, f32)>,
+    #[arg(long, requires = "dog", help = "Stretch the --dog result so the strongest line is white")]
+    dog_normalize: bool,
+    #[arg(
+        long,
+        value_parser = parse_fraction,
+        value_name = "STRENGTH",
+        help = "Darken the corners, 0.0 leaves the image unchanged and 1.0 turns the corners black"
+    )]
+    vignette: Option<f32>,
+    #[arg(
+        long,
+        value_parser = parse_bloom,
+        value_name = "THRESHOLD,INTENSITY",
+        help = "Make areas with luma >= THRESHOLD glow, INTENSITY scales the glow (1.0 adds it at full strength)"
+    )]
+    bloom: Option<(u8, f32)>,
+    #[arg(
+        long,
+        value_parser = parse_oil,
+        value_name = "RADIUS,INTENSITY",
+        help = "Oil painting effect with INTENSITY brightness levels, e.g. 3,20; slow, the cost grows with the square of RADIUS"
+    )]
+    oil: Option<(u32, u32)>,
+    #[arg(
+        long,
+        value_parser = parse_colorkey,
+        value_name = "R,G,B,TOLERANCE",
+        help = "Make pixels within TOLERANCE of the color transparent"
+    )]
+    colorkey: Option<(u8, u8, u8, f32)>,
+    #[arg(long, help = "Make everything outside the centered circle transparent, for avatars")]
+    circle: bool,
+    #[arg(long, value_name = "RADIUS", help = "Round the corners off with transparent quarter circles")]
+    round_corners: Option<u32>,
+    #[arg(
+        long,
+        value_parser = parse_crop,
+        value_name = "X,Y,W,H",
+        conflicts_with = "stream",
+        help = "Apply the transforms only inside this region, leaving the rest of the image unchanged"
+    )]
+    region: Option<(u32, u32, u32, u32)>,
+    #[arg(
+        long = "as",
+        value_enum,
+        value_name = "COLOR_TYPE",
+        help = "Convert the result to this color type, applied after every other operation"
+    )]
+    convert_to: Option<ColorTarget>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Comma separated operations applied in order, e.g. 'blur=2.0,rotate=90,grayscale' \
+                (multi-value steps such as crop=X:Y:W:H use ':'); overrides the individual transform flags"
+    )]
+    pipeline: Option<Vec<Operation>>,
+    #[arg(
+        long,
+        value_parser = load_preset,
+        value_name = "FILE",
+        help = "TOML file with [[op]] tables applied before the --pipeline or individual transform flags"
+    )]
+    preset: Option<Preset>,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum OutputFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Webp,
+}
+
+impl From<OutputFormat> for ImageFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Bmp => ImageFormat::Bmp,
+            OutputFormat::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SaveOptions {
+    format: Option<ImageFormat>,
+    // The format of the image that was read, used for outfiles without an extension.
+    input_format: Option<ImageFormat>,
+    quality: Option<u8>,
+    lossless: bool,
+    print_hash: bool,
+    flatten: Option<[u8; 3]>,
+}
+
+impl SaveOptions {
+    fn from_cli(cli: &Cli) -> Self {
+        SaveOptions {
+            format: cli.format.map(ImageFormat::from),
+            input_format: None,
+            quality: cli.quality,
+            lossless: cli.lossless,
+            print_hash: cli.print_hash,
+            flatten: cli.flatten,
+        }
+    }
+
+    fn keeping_format_of(self, infile: &str) -> Self {
+        SaveOptions { input_format: detect_format(infile), ..self }
+    }
 }
 
 #[derive(Subcommand, Clone)]
 enum Commands {
-    // Transform an image
+    // Transform an image, use '-' to read from stdin or write to stdout; INFILE may also be an http(s) URL
     Transform {
         infile: String,
+        #[arg(required_unless_present = "out")]
+        outfile: Option<String>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Also write the result to FILE, may be repeated; each file gets the format of its extension"
+        )]
+        out: Vec<String>,
+    },
+    // Apply the transform flags to every image in INDIR, writing to OUTDIR
+    Batch {
+        #[arg(required_unless_present = "from_file")]
+        indir: Option<String>,
+        #[arg(required_unless_present = "from_file")]
+        outdir: Option<String>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["indir", "outdir"],
+            help = "Process the INFILE<TAB>OUTFILE pairs listed one per line in FILE instead of a directory"
+        )]
+        from_file: Option<String>,
+        #[arg(
+            long,
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "Number of files processed in parallel, defaults to the number of CPUs"
+        )]
+        jobs: Option<u32>,
+    },
+    // Print information about an image without modifying it
+    Info {
+        infile: String,
+        #[arg(long, help = "Print the information as JSON")]
+        json: bool,
+    },
+    // Print the most common colors of INFILE as hex codes, most common first
+    Palette {
+        infile: String,
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..=256), help = "Number of colors")]
+        count: u32,
+        #[arg(long, value_name = "FILE", help = "Also write the colors as a row of squares")]
+        swatch: Option<String>,
+    },
+    // Print the min, max, mean and standard deviation of each channel of INFILE
+    Stats {
+        infile: String,
+        #[arg(long, help = "Print the statistics as JSON")]
+        json: bool,
+    },
+    // Print INFILE as ASCII art, brighter pixels using denser characters
+    Ascii {
+        infile: String,
+        #[arg(long, default_value_t = 80, value_parser = clap::value_parser!(u32).range(1..), help = "Characters per line")]
+        cols: u32,
+        #[arg(long, help = "Use denser characters for darker pixels, for dark text on a light background")]
+        invert: bool,
+        #[arg(long, value_name = "FILE", help = "Write the text to FILE instead of stdout")]
+        out: Option<String>,
+    },
+    // Print a shell completion script, e.g. `source <(mirage completions bash)`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    // Write a man page for the tool to OUTDIR/mirage.1
+    Manpage {
+        outdir: String,
+    },
+    // Report how much two images of the same size differ
+    Compare {
+        a: String,
+        b: String,
+        #[arg(long, value_name = "FILE", help = "Write an image highlighting the differing pixels in red")]
+        diff: Option<String>,
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Exit with an error when any channel differs by more than THRESHOLD"
+        )]
+        threshold: u8,
+    },
+    // Count the pixels at each channel value, as CSV or as a chart image
+    #[command(group(ArgGroup::new("histogram_output").args(["csv", "out"]).required(true).multiple(true)))]
+    Histogram {
+        infile: String,
+        #[arg(long, help = "Print the counts as CSV with one bin,r,g,b row per value")]
+        csv: bool,
+        #[arg(long, value_name = "FILE", help = "Render the histogram as a 256 pixel wide chart")]
+        out: Option<String>,
+        #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..), help = "Height of the chart")]
+        chart_height: u32,
+    },
+    // Composite OVERLAY on top of BASE
+    Overlay {
+        base: String,
+        overlay: String,
+        outfile: String,
+        #[arg(long, allow_hyphen_values = true, default_value_t = 0)]
+        x: i64,
+        #[arg(long, allow_hyphen_values = true, default_value_t = 0)]
+        y: i64,
+        #[arg(long, value_parser = parse_opacity, default_value_t = 1.0)]
+        opacity: f32,
+        #[arg(long, conflicts_with_all = ["x", "y"], help = "Repeat OVERLAY diagonally across all of BASE")]
+        tile: bool,
+        #[arg(long, value_name = "PIXELS", default_value_t = 0, requires = "tile", help = "Gap between tiled copies")]
+        spacing: u32,
+    },
+    // Repeat INFILE in a grid of ROWS by COLS copies
+    Tile {
+        infile: String,
+        outfile: String,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 2)]
+        rows: u32,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 2)]
+        cols: u32,
+    },
+    // Cut INFILE into ROWS by COLS pieces named tile_{row}_{col}.png
+    Split {
+        infile: String,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 2)]
+        rows: u32,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 2)]
+        cols: u32,
+        #[arg(long, help = "Directory for the pieces, created if missing")]
+        outdir: String,
+    },
+    // Draw a caption onto INFILE, '\n' in the text starts a new line
+    Text {
+        infile: String,
+        outfile: String,
+        #[arg(long)]
+        text: String,
+        #[arg(long, allow_hyphen_values = true, default_value_t = 10)]
+        x: i32,
+        #[arg(long, allow_hyphen_values = true, default_value_t = 10)]
+        y: i32,
+        #[arg(long, value_parser = parse_positive, default_value_t = 32.0, help = "Font size in pixels")]
+        size: f64,
+        #[arg(long, value_parser = parse_rgb, value_name = "R,G,B", default_value = "255,255,255")]
+        color: [u8; 3],
+        #[arg(long, value_name = "PATH", help = "TrueType font to use instead of the bundled DejaVu Sans")]
+        font: Option<String>,
+    },
+    // Arrange several images in a grid, e.g. for a contact sheet
+    Montage {
         outfile: String,
+        #[arg(required = true)]
+        infiles: Vec<String>,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 4)]
+        cols: u32,
+        #[arg(long, default_value_t = 0)]
+        pad: u32,
+        #[arg(long, value_parser = parse_rgb, value_name = "R,G,B", default_value = "255,255,255")]
+        bg: [u8; 3],
+        #[arg(
+            long,
+            value_parser = parse_dimensions,
+            value_name = "WIDTHxHEIGHT",
+            help = "Cell size, defaults to the size of the largest image"
+        )]
+        cell: Option<(u32, u32)>,
     },
     // Generate a fractal image
     Fractal {
         outfile: String,
+        #[arg(long, allow_hyphen_values = true, default_value_t = -0.4)]
+        cx: f32,
+        #[arg(long, allow_hyphen_values = true, default_value_t = 0.6)]
+        cy: f32,
+        #[arg(long, default_value_t = 255)]
+        max_iter: u32,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+        #[arg(long, value_enum, default_value_t = Colormap::Classic, help = "Palette for the iteration counts")]
+        colormap: Colormap,
+    },
+    // Generate a poster of Julia sets for a range of c constants
+    FractalGrid {
+        outfile: String,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 3)]
+        rows: u32,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 3)]
+        cols: u32,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 256)]
+        cell_width: u32,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 256)]
+        cell_height: u32,
+        #[arg(long, value_enum, default_value_t = Colormap::Classic, help = "Palette for the iteration counts")]
+        colormap: Colormap,
+    },
+    // Generate a Mandelbrot set image
+    Mandelbrot {
+        outfile: String,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+        #[arg(long, default_value_t = 256)]
+        max_iter: u32,
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true, default_value = "-0.5,0")]
+        center: (f64, f64),
+        #[arg(long, default_value_t = 1.0)]
+        zoom: f64,
+        #[arg(long, value_enum, default_value_t = Colormap::Classic, help = "Palette for the iteration counts")]
+        colormap: Colormap,
+    },
+    // Generate a Burning Ship fractal image
+    BurningShip {
+        outfile: String,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+        #[arg(long, default_value_t = 256)]
+        max_iter: u32,
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true, default_value = "-1.75,-0.03")]
+        center: (f64, f64),
+        #[arg(long, default_value_t = 20.0)]
+        zoom: f64,
+        #[arg(long, value_enum, default_value_t = Colormap::Classic, help = "Palette for the iteration counts")]
+        colormap: Colormap,
+    },
+    // Generate an animated GIF zooming into the Mandelbrot set
+    FractalAnim {
+        outfile: String,
+        #[arg(long, default_value_t = 30)]
+        frames: u32,
+        #[arg(long, default_value_t = 1.0)]
+        zoom_start: f64,
+        #[arg(long, default_value_t = 100.0)]
+        zoom_end: f64,
+        #[arg(long, value_parser = parse_point, allow_hyphen_values = true, default_value = "-0.743643,0.131825")]
+        center: (f64, f64),
+        #[arg(long, default_value_t = 10)]
+        fps: u32,
+        #[arg(long, default_value_t = 400)]
+        width: u32,
+        #[arg(long, default_value_t = 400)]
+        height: u32,
+        #[arg(long, default_value_t = 256)]
+        max_iter: u32,
+        #[arg(long, value_enum, default_value_t = Colormap::Classic, help = "Palette for the iteration counts")]
+        colormap: Colormap,
     },
     // Generate a simple image
     Generate {
         outfile: String,
     },
+    // Generate a linear gradient between two colors
+    Gradient {
+        outfile: String,
+        #[arg(long, value_parser = parse_rgb, value_name = "R,G,B", default_value = "0,0,0")]
+        from: [u8; 3],
+        #[arg(long, value_parser = parse_rgb, value_name = "R,G,B", default_value = "255,255,255")]
+        to: [u8; 3],
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+        #[arg(long, value_enum, default_value_t = Direction::Horizontal)]
+        direction: Direction,
+    },
+    // Generate reproducible noise from a seed
+    Noise {
+        outfile: String,
+        #[arg(long = "type", value_enum, default_value_t = NoiseKind::White)]
+        kind: NoiseKind,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+    },
+    // Generate a colorful plasma from overlapping sine waves
+    Plasma {
+        outfile: String,
+        #[arg(long, value_parser = parse_positive, default_value_t = 32.0)]
+        scale: f64,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+    },
+    // Generate an image filled with a single color
+    Solid {
+        outfile: String,
+        #[arg(long, value_parser = parse_rgba, value_name = "R,G,B,A", default_value = "255,255,255,255")]
+        color: [u8; 4],
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+    },
+    // Generate a checkerboard pattern
+    Checkerboard {
+        outfile: String,
+        #[arg(long, default_value_t = 8)]
+        cell_size: u32,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+        #[arg(long, value_parser = parse_rgb, value_name = "R,G,B", default_value = "0,0,0")]
+        color1: [u8; 3],
+        #[arg(long, value_parser = parse_rgb, value_name = "R,G,B", default_value = "255,255,255")]
+        color2: [u8; 3],
+    },
+}
+
+#[derive(Debug)]
+enum ProcessError {
+    Load(ImageError),
+    Fetch(String, FetchError),
+    Save(ImageError),
+    UnsupportedFormat(String),
+    Io(io::Error),
+    BatchFailed(usize),
+    IgnoredOptions(String),
+    InvalidManifest(String),
+    Compare(String),
+    InvalidRegion(String),
+    Interrupted(String),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::Load(err) => write!(f, "Failed to open INFILE: {}", err),
+            ProcessError::Fetch(url, err) => write!(f, "Failed to download {}: {}", url, err),
+            ProcessError::Save(err) => write!(f, "Failed writing OUTFILE: {}", err),
+            ProcessError::UnsupportedFormat(path) => write!(f, "Unsupported image format: {}", path),
+            ProcessError::Io(err) => write!(f, "{}", err),
+            ProcessError::BatchFailed(count) => write!(f, "{} file(s) failed to process", count),
+            ProcessError::IgnoredOptions(message) => write!(f, "{}", message),
+            ProcessError::InvalidManifest(message) => write!(f, "Invalid manifest {}", message),
+            ProcessError::Compare(message) => write!(f, "{}", message),
+            ProcessError::InvalidRegion(message) => write!(f, "Can't limit to --region: {}", message),
+            ProcessError::Interrupted(message) => write!(f, "Interrupted, {}", message),
+        }
+    }
+}
+
+impl ProcessError {
+    // A stable name for the type of error, used by --log-format json.
+    fn kind(&self) -> &'static str {
+        match self {
+            ProcessError::Load(_) => "load",
+            ProcessError::Fetch(..) => "fetch",
+            ProcessError::Save(_) => "save",
+            ProcessError::UnsupportedFormat(_) => "unsupported-format",
+            ProcessError::Io(_) => "io",
+            ProcessError::BatchFailed(_) => "batch-failed",
+            ProcessError::IgnoredOptions(_) => "ignored-options",
+            ProcessError::InvalidManifest(_) => "invalid-manifest",
+            ProcessError::Compare(_) => "compare",
+            ProcessError::InvalidRegion(_) => "invalid-region",
+            ProcessError::Interrupted(_) => "interrupted",
+        }
+    }
+
+    // 130 is what shells report for a process killed by SIGINT.
+    fn exit_code(&self) -> i32 {
+        match self {
+            ProcessError::Interrupted(_) => 130,
+            _ => 1,
+        }
+    }
+}
+
+impl Error for ProcessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProcessError::Load(err) | ProcessError::Save(err) => Some(err),
+            ProcessError::Io(err) => Some(err),
+            ProcessError::Fetch(_, err) => Some(err),
+            ProcessError::UnsupportedFormat(_) | ProcessError::BatchFailed(_) | ProcessError::IgnoredOptions(_)
+            | ProcessError::InvalidManifest(_)
+            | ProcessError::Compare(_)
+            | ProcessError::InvalidRegion(_)
+            | ProcessError::Interrupted(_) => None,
+        }
+    }
+}
+
+impl Commands {
+    // Whether the command stops early once progress::cancel() is called.
+    fn is_cancellable(&self) -> bool {
+        matches!(
+            self,
+            Commands::Batch { .. }
+                | Commands::Fractal { .. }
+                | Commands::FractalGrid { .. }
+                | Commands::Mandelbrot { .. }
+                | Commands::BurningShip { .. }
+                | Commands::FractalAnim { .. }
+                | Commands::Generate { .. }
+                | Commands::Gradient { .. }
+                | Commands::Noise { .. }
+                | Commands::Plasma { .. }
+                | Commands::Solid { .. }
+                | Commands::Checkerboard { .. }
+        )
+    }
+
+    // Whether the command runs the transform options on its images.
+    fn applies_transforms(&self) -> bool {
+        matches!(
+            self,
+            Commands::Transform { .. }
+                | Commands::Batch { .. }
+                | Commands::Fractal { .. }
+                | Commands::FractalGrid { .. }
+                | Commands::Mandelbrot { .. }
+                | Commands::BurningShip { .. }
+                | Commands::FractalAnim { .. }
+                | Commands::Generate { .. }
+                | Commands::Gradient { .. }
+                | Commands::Noise { .. }
+                | Commands::Plasma { .. }
+                | Commands::Solid { .. }
+                | Commands::Checkerboard { .. }
+        )
+    }
+
+    fn outfiles(&self) -> Vec<&str> {
+        match self {
+            Commands::Transform { outfile, out, .. } => outfile.iter().chain(out).map(String::as_str).collect(),
+            Commands::Fractal { outfile, .. }
+            | Commands::FractalGrid { outfile, .. }
+            | Commands::Mandelbrot { outfile, .. }
+            | Commands::BurningShip { outfile, .. }
+            | Commands::FractalAnim { outfile, .. }
+            | Commands::Generate { outfile }
+            | Commands::Gradient { outfile, .. }
+            | Commands::Noise { outfile, .. }
+            | Commands::Plasma { outfile, .. }
+            | Commands::Solid { outfile, .. }
+            | Commands::Checkerboard { outfile, .. }
+            | Commands::Overlay { outfile, .. }
+            | Commands::Tile { outfile, .. }
+            | Commands::Montage { outfile, .. }
+            | Commands::Text { outfile, .. } => vec![outfile],
+            Commands::Histogram { out, .. } => out.as_deref().into_iter().collect(),
+            Commands::Compare { diff, .. } => diff.as_deref().into_iter().collect(),
+            Commands::Palette { swatch, .. } => swatch.as_deref().into_iter().collect(),
+            Commands::Batch { .. }
+            | Commands::Split { .. }
+            | Commands::Info { .. }
+            | Commands::Stats { .. }
+            | Commands::Ascii { .. }
+            | Commands::Completions { .. }
+            | Commands::Manpage { .. } => Vec::new(),
+        }
+    }
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let start = Instant::now();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    log::set_quiet(cli.quiet);
+    log::set_format(cli.log_format);
+    fetch::set_timeout(cli.timeout);
+    fetch::set_retries(cli.retries);
+    // Commands that check for cancellation get to stop cleanly on the first
+    // Ctrl-C, a second one (or any Ctrl-C for the other commands) exits at once.
+    let cancellable = cli.command.as_ref().is_some_and(Commands::is_cancellable);
+    let _ = ctrlc::set_handler(move || {
+        if !cancellable || progress::cancel() {
+            exit(130);
+        }
+    });
+    timing::set_enabled(cli.timings);
+    let result = check_ignored_options(&cli, &matches).and_then(|()| run(cli));
+    if timing::is_enabled() {
+        log::info(&format_timing("total", start.elapsed()));
+    }
+    if let Err(err) = result {
+        log::error(err.kind(), &err.to_string());
+        exit(err.exit_code());
+    }
+}
+
+// Transform options only affect some commands. Passing them to any other
+// command is a warning, or an error with --strict.
+fn check_ignored_options(cli: &Cli, matches: &ArgMatches) -> Result<(), ProcessError> {
+    let (Some(command), Some(name)) = (&cli.command, matches.subcommand_name()) else {
+        return Ok(());
+    };
+    if command.applies_transforms() {
+        return Ok(());
+    }
+
+    let ignored: Vec<String> = Cli::command()
+        .get_arguments()
+        .filter(|arg| arg.get_help_heading() == Some(TRANSFORM_HEADING))
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .map(|arg| format!("--{}", arg.get_long().unwrap_or(arg.get_id().as_str())))
+        .collect();
+    if ignored.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!("{} {} no effect on the {} command", ignored.join(", "), if ignored.len() == 1 { "has" } else { "have" }, name);
+    if cli.strict {
+        return Err(ProcessError::IgnoredOptions(message));
+    }
+    log::warn(&message);
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), ProcessError> {
+    progress::set_enabled(cli.verbose);
+
+    if cli.dry_run {
+        for op in build_operations(&cli) {
+            println!("{}", op);
+        }
+        return Ok(());
+    }
+
+    if cli.transforms.region.is_some() {
+        if let Some(op) = build_operations(&cli).iter().find(|op| !op.keeps_dimensions()) {
+            return Err(ProcessError::InvalidRegion(format!("{} changes the image size", op)));
+        }
+    }
+
+    // Check the output before any expensive loading or generating happens.
+    // Outfiles without an extension get one when they are saved.
+    let named = |outfile: &&str| *outfile != "-" && Path::new(outfile).extension().is_some();
+    let outfiles = cli.command.as_ref().map(Commands::outfiles).unwrap_or_default();
+    for outfile in outfiles.into_iter().filter(named) {
+        let format = validate_output_format(outfile)?;
+        check_encoder(outfile, format, cli.quality, cli.lossless)?;
+        if matches!(cli.command, Some(Commands::FractalAnim { .. })) && format != ImageFormat::Gif {
+            return Err(ProcessError::UnsupportedFormat(format!("{} (animations are written as GIF)", outfile)));
+        }
+        let masking: Vec<String> =
+            build_operations(&cli).iter().filter(|op| op.adds_transparency()).map(Operation::to_string).collect();
+        if !masking.is_empty() && !stores_alpha(format) && cli.flatten.is_none() {
+            log::warn(&format!(
+                "{} can't store the transparency added by {}, pass --flatten to choose a background",
+                outfile,
+                masking.join(", ")
+            ));
+        }
+    }
 
     match cli.command {
-        Commands::Transform { .. } => {
-            handle_image_processing(cli);
+        Some(Commands::Transform { .. }) => handle_image_processing(cli),
+        Some(Commands::Batch { .. }) => handle_batch(cli),
+        Some(Commands::Overlay { .. }) => handle_overlay(cli),
+        Some(Commands::Tile { .. }) => handle_tile(cli),
+        Some(Commands::Split { .. }) => handle_split(cli),
+        Some(Commands::Montage { .. }) => handle_montage(cli),
+        Some(Commands::Text { .. }) => handle_text(cli),
+        Some(Commands::Info { .. }) => handle_info(cli),
+        Some(Commands::Stats { .. }) => handle_stats(cli),
+        Some(Commands::Palette { .. }) => handle_palette(cli),
+        Some(Commands::Ascii { .. }) => handle_ascii(cli),
+        Some(Commands::Histogram { .. }) => handle_histogram(cli),
+        Some(Commands::Compare { .. }) => handle_compare(cli),
+        Some(Commands::Manpage { .. }) => handle_manpage(cli),
+        Some(Commands::Completions { shell }) => {
+            // Complete the installed binary rather than the display name of the command.
+            clap_complete::generate(shell, &mut Cli::command(), env!("CARGO_BIN_NAME"), &mut io::stdout());
+            Ok(())
         }
-        command => handle_image_generation(command),
+        Some(_) => handle_image_generation(cli),
+        None if cli.list_formats => {
+            print_formats();
+            Ok(())
+        }
+        None => print_usage_and_exit(),
     }
 }
 
-fn handle_image_processing(cli: Cli) {
-    if let Commands::Transform { infile, outfile } = &cli.command {
-        let img = load_image(&infile);
+fn handle_image_processing(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(command @ Commands::Transform { infile, .. }) = &cli.command {
+        // Every output is written from the same processed image, except that
+        // streaming reads INFILE again for each one.
+        let outfiles = command.outfiles();
+        if cli.stream {
+            return outfiles.into_iter().try_for_each(|outfile| stream_image(infile, outfile, &cli));
+        }
+        let img = load_image(infile, cli.auto_orient)?;
+        if matches!(img.color(), ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16) {
+            let truncating: Vec<String> =
+                build_operations(&cli).iter().filter(|op| !op.keeps_depth()).map(Operation::to_string).collect();
+            if !truncating.is_empty() {
+                log::warn(&format!("{} reduce {} to 8 bits per channel", truncating.join(", "), infile));
+            }
+        }
         let img = process_image(img, &cli);
-        save_image(img, &outfile);
+        let options = SaveOptions::from_cli(&cli).keeping_format_of(infile);
+        outfiles.into_iter().try_for_each(|outfile| save_image(img.clone(), outfile, options))
     } else {
         print_usage_and_exit();
     }
 }
 
-fn handle_image_generation(command: Commands) {
-    match command {
-        Commands::Fractal { outfile } => {
-            let img = fractal();
-            save_image(img, &outfile);
+fn stream_image(infile: &str, outfile: &str, cli: &Cli) -> Result<(), ProcessError> {
+    if is_url(infile) {
+        return Err(ProcessError::UnsupportedFormat(format!("{} (--stream only supports local files)", infile)));
+    }
+    for path in [infile, outfile] {
+        if ImageFormat::from_path(path).ok() != Some(ImageFormat::Png) {
+            return Err(ProcessError::UnsupportedFormat(format!("{} (--stream only supports PNG files)", path)));
         }
-        Commands::Generate { outfile } => {
-            let img = generate();
-            save_image(img, &outfile);
+    }
+    stream_png(Path::new(infile), Path::new(outfile), &build_operations(cli)).map_err(ProcessError::Io)
+}
+
+fn handle_batch(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Batch { indir, outdir, from_file, jobs }) = &cli.command {
+        let files = match (from_file, indir, outdir) {
+            (Some(manifest), _, _) => {
+                let contents = fs::read_to_string(manifest).map_err(ProcessError::Io)?;
+                let files = parse_manifest(&contents)
+                    .map_err(|err| ProcessError::InvalidManifest(format!("{}: {}", manifest, err)))?;
+                for parent in files.iter().filter_map(|(_, outfile)| outfile.parent()) {
+                    fs::create_dir_all(parent).map_err(ProcessError::Io)?;
+                }
+                files
+            }
+            (None, Some(indir), Some(outdir)) => {
+                fs::create_dir_all(outdir).map_err(ProcessError::Io)?;
+                let infiles = find_images(Path::new(indir))?;
+                infiles
+                    .into_iter()
+                    .map(|infile| {
+                        let outfile = Path::new(outdir).join(infile.file_name().unwrap());
+                        (infile, outfile)
+                    })
+                    .collect()
+            }
+            _ => print_usage_and_exit(),
+        };
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(jobs.unwrap_or(0) as usize)
+            .build()
+            .map_err(|err| ProcessError::Io(io::Error::other(err)))?;
+        // Each worker loads, transforms and encodes its own file, so encoding
+        // runs in parallel too. A panic while processing one file counts as a
+        // failure of that file instead of taking down the whole batch. Once
+        // cancelled, files already started are finished and the rest skipped.
+        let outcomes: Vec<Option<bool>> = pool.install(|| {
+            files
+                .par_iter()
+                .enumerate()
+                .map(|(i, (infile, outfile))| {
+                    if progress::is_cancelled() {
+                        return None;
+                    }
+                    if cli.verbose {
+                        log::info(&format!("file {}/{}: {}", i + 1, files.len(), infile.display()));
+                    }
+                    let failed = match panic::catch_unwind(AssertUnwindSafe(|| process_file(infile, outfile, &cli))) {
+                        Ok(Ok(())) => false,
+                        Ok(Err(err)) => {
+                            log::error(err.kind(), &format!("{}: {}", infile.display(), err));
+                            true
+                        }
+                        Err(_) => {
+                            log::error("panic", &format!("{}: panicked while processing", infile.display()));
+                            true
+                        }
+                    };
+                    Some(failed)
+                })
+                .collect()
+        });
+        let processed = outcomes.iter().flatten().count();
+        let failed = outcomes.iter().flatten().filter(|&&failed| failed).count();
+
+        if !log::is_quiet() {
+            println!("{} succeeded, {} failed", processed - failed, failed);
         }
-        _ => print_usage_and_exit(),
+        if processed < files.len() {
+            return Err(ProcessError::Interrupted(format!("{} of {} file(s) were processed", processed, files.len())));
+        }
+        if failed > 0 {
+            return Err(ProcessError::BatchFailed(failed));
+        }
+        Ok(())
+    } else {
+        print_usage_and_exit();
     }
 }
 
-fn process_image(img: DynamicImage, cli: &Cli) -> DynamicImage {
-    let mut img = img;
+fn process_file(infile: &Path, outfile: &Path, cli: &Cli) -> Result<(), ProcessError> {
+    let img = load_image(&infile.to_string_lossy(), cli.auto_orient)?;
+    let img = process_image(img, cli);
+    let infile = infile.to_string_lossy();
+    save_image(img, &outfile.to_string_lossy(), SaveOptions::from_cli(cli).keeping_format_of(&infile))
+}
 
-    if let Some(sigma) = cli.blur {
-        img = blur(img, sigma);
+// Lists the files directly inside `dir` whose extension is a readable image format.
+fn find_images(dir: &Path) -> Result<Vec<PathBuf>, ProcessError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).map_err(ProcessError::Io)? {
+        let path = entry.map_err(ProcessError::Io)?.path();
+        let readable = ImageFormat::from_path(&path).is_ok_and(|format| format.reading_enabled());
+        if path.is_file() && readable {
+            paths.push(path);
+        }
     }
+    paths.sort();
+    Ok(paths)
+}
 
-    if let Some(value) = cli.brighten {
-        img = brighten(img, value);
+fn handle_info(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Info { infile, json }) = &cli.command {
+        let img = load_image(infile, false)?;
+        let info = ImageInfo::new(&img, detect_format(infile));
+        if *json {
+            println!("{}", info.to_json());
+        } else {
+            print!("{}", info);
+        }
+        Ok(())
+    } else {
+        print_usage_and_exit();
     }
+}
 
-    if let Some((x, y, width, height)) = cli.crop {
-        img = crop(img, x, y, width, height);
+fn handle_stats(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Stats { infile, json }) = &cli.command {
+        let stats = stats(&load_image(infile, false)?);
+        if *json {
+            println!("{}", stats.to_json());
+        } else {
+            print!("{}", stats);
+        }
+        Ok(())
+    } else {
+        print_usage_and_exit();
+    }
+}
+
+fn handle_palette(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Palette { infile, count, swatch }) = &cli.command {
+        let colors = dominant_colors(&load_image(infile, cli.auto_orient)?, *count as usize);
+        for color in &colors {
+            println!("{}", hex_color(*color));
+        }
+        if let Some(swatch) = swatch {
+            save_image(palette_swatch(&colors, SWATCH_SIZE), swatch, SaveOptions::from_cli(&cli))?;
+        }
+        Ok(())
+    } else {
+        print_usage_and_exit();
+    }
+}
+
+fn handle_ascii(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Ascii { infile, cols, invert, out }) = &cli.command {
+        let art = to_ascii(&load_image(infile, cli.auto_orient)?, *cols, *invert);
+        match out {
+            Some(out) => fs::write(out, art).map_err(ProcessError::Io),
+            None => {
+                print!("{}", art);
+                Ok(())
+            }
+        }
+    } else {
+        print_usage_and_exit();
     }
+}
 
-    if let Some(value) = cli.rotate {
-        img = rotate(img, value);
+fn handle_manpage(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Manpage { outdir }) = &cli.command {
+        fs::create_dir_all(outdir).map_err(ProcessError::Io)?;
+        let path = Path::new(outdir).join(format!("{}.1", env!("CARGO_BIN_NAME")));
+        let mut file = BufWriter::new(fs::File::create(&path).map_err(ProcessError::Io)?);
+        Man::new(Cli::command().name(env!("CARGO_BIN_NAME")))
+            .render(&mut file)
+            .and_then(|()| file.flush())
+            .map_err(ProcessError::Io)?;
+        println!("{}", path.display());
+        Ok(())
+    } else {
+        print_usage_and_exit();
     }
+}
 
-    if cli.invert {
-        img = invert(img);
+fn handle_compare(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Compare { a, b, diff, threshold }) = &cli.command {
+        let (a, b) = (load_image(a, cli.auto_orient)?, load_image(b, cli.auto_orient)?);
+        let report = compare(&a, &b).map_err(ProcessError::Compare)?;
+        print!("{}", report);
+        if let Some(diff) = diff {
+            let img = diff_image(&a, &b).map_err(ProcessError::Compare)?;
+            save_image(img, diff, SaveOptions::from_cli(&cli))?;
+        }
+        if report.max > *threshold {
+            return Err(ProcessError::Compare(format!(
+                "Images differ by up to {} (threshold {})",
+                report.max, threshold
+            )));
+        }
+        Ok(())
+    } else {
+        print_usage_and_exit();
     }
+}
 
-    if cli.grayscale {
-        img = grayscale(img);
+fn handle_histogram(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Histogram { infile, csv, out, chart_height }) = &cli.command {
+        let img = load_image(infile, cli.auto_orient)?;
+        let hist = histogram(&img);
+        if *csv {
+            print!("{}", histogram_csv(&hist));
+        }
+        if let Some(out) = out {
+            save_image(histogram_chart(&hist, *chart_height), out, SaveOptions::from_cli(&cli))?;
+        }
+        Ok(())
+    } else {
+        print_usage_and_exit();
     }
+}
 
-    img
+// Guesses the format from the file contents, falling back to the extension.
+fn detect_format(infile: &str) -> Option<ImageFormat> {
+    if infile == "-" || is_url(infile) {
+        return None;
+    }
+    ImageReader::open(infile)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.format())
 }
 
-fn print_usage_and_exit() {
-    Cli::command().print_help().unwrap();
-    exit(-1);
+fn handle_overlay(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Overlay { base: infile, overlay: top, outfile, x, y, opacity, tile, spacing }) = &cli.command {
+        let base = load_image(infile, cli.auto_orient)?;
+        let top = load_image(top, cli.auto_orient)?;
+        let img = if *tile {
+            watermark_tiled(base, top, *opacity, *spacing)
+        } else {
+            overlay(base, top, *x, *y, *opacity)
+        };
+        save_image(img, outfile, SaveOptions::from_cli(&cli).keeping_format_of(infile))
+    } else {
+        print_usage_and_exit();
+    }
 }
 
-fn load_image(infile: &str) -> DynamicImage {
-    image::open(infile).expect("Failed to open INFILE.")
+fn handle_tile(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Tile { infile, outfile, rows, cols }) = &cli.command {
+        let img = load_image(infile, cli.auto_orient)?;
+        save_image(tile(img, *rows, *cols), outfile, SaveOptions::from_cli(&cli).keeping_format_of(infile))
+    } else {
+        print_usage_and_exit();
+    }
+}
+
+fn handle_split(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Split { infile, rows, cols, outdir }) = &cli.command {
+        let img = load_image(infile, cli.auto_orient)?;
+        fs::create_dir_all(outdir).map_err(ProcessError::Io)?;
+        let cols = (*cols).min(img.width()).max(1) as usize;
+        for (i, piece) in split(&img, *rows, cols as u32).into_iter().enumerate() {
+            let outfile = Path::new(outdir).join(format!("tile_{}_{}.png", i / cols, i % cols));
+            save_image(piece, &outfile.to_string_lossy(), SaveOptions::from_cli(&cli))?;
+        }
+        Ok(())
+    } else {
+        print_usage_and_exit();
+    }
+}
+
+fn handle_text(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Text { infile, outfile, text, x, y, size, color, font }) = &cli.command {
+        let font = match font {
+            Some(path) => {
+                let bytes = fs::read(path).map_err(ProcessError::Io)?;
+                let invalid = || ProcessError::UnsupportedFormat(format!("{} (not a TrueType font)", path));
+                load_font(bytes).ok_or_else(invalid)?
+            }
+            None => default_font(),
+        };
+        // Shells pass a typed "\n" through as a backslash and an n.
+        let text = text.replace("\\n", "\n");
+        let img = load_image(infile, cli.auto_orient)?;
+        let img = draw_text(img, &text, *x, *y, *size as f32, *color, &font);
+        save_image(img, outfile, SaveOptions::from_cli(&cli).keeping_format_of(infile))
+    } else {
+        print_usage_and_exit();
+    }
+}
+
+// Inputs that fail to load are reported and left out of the montage, which
+// only fails when none of them could be loaded.
+fn handle_montage(cli: Cli) -> Result<(), ProcessError> {
+    if let Some(Commands::Montage { outfile, infiles, cols, pad, bg, cell }) = &cli.command {
+        let mut images = Vec::new();
+        for infile in infiles {
+            match load_image(infile, cli.auto_orient) {
+                Ok(img) => images.push(img),
+                Err(err) => log::warn(&format!("Skipping {}: {}", infile, err)),
+            }
+        }
+        if images.is_empty() {
+            return Err(ProcessError::BatchFailed(infiles.len()));
+        }
+
+        let img = montage(&images, *cols, *pad, *bg, *cell);
+        save_image(img, outfile, SaveOptions::from_cli(&cli))
+    } else {
+        print_usage_and_exit();
+    }
 }
 
-fn save_image(img: DynamicImage, outfile: &str) {
-    img.save(outfile).expect("Failed writing OUTFILE.")
+// Generated images go through the same transform options as loaded ones.
+fn handle_image_generation(cli: Cli) -> Result<(), ProcessError> {
+    let (img, outfile) = match cli.command.clone() {
+        Some(Commands::Fractal { outfile, cx, cy, max_iter, width, height, colormap }) => {
+            (fractal(cx, cy, max_iter, width, height, colormap), outfile)
+        }
+        Some(Commands::FractalGrid { outfile, rows, cols, cell_width, cell_height, colormap }) => {
+            (fractal_grid(rows, cols, cell_width, cell_height, colormap), outfile)
+        }
+        Some(Commands::Mandelbrot { outfile, width, height, max_iter, center, zoom, colormap }) => {
+            (mandelbrot(width, height, max_iter, center, zoom, colormap), outfile)
+        }
+        Some(Commands::BurningShip { outfile, width, height, max_iter, center, zoom, colormap }) => {
+            (burning_ship(width, height, max_iter, center, zoom, colormap), outfile)
+        }
+        Some(Commands::FractalAnim {
+            outfile,
+            frames,
+            zoom_start,
+            zoom_end,
+            center,
+            fps,
+            width,
+            height,
+            max_iter,
+            colormap,
+        }) => {
+            let frames = mandelbrot_zoom(width, height, max_iter, center, (zoom_start, zoom_end), frames, colormap);
+            check_cancelled()?;
+            let frames = frames.into_iter().map(|frame| process_image(frame, &cli)).collect();
+            let outfile = with_extension(&outfile, ImageFormat::Gif);
+            return timing::time(|| format!("save {}", outfile), || save_animation(frames, &outfile, fps));
+        }
+        Some(Commands::Generate { outfile }) => (generate(), outfile),
+        Some(Commands::Gradient { outfile, from, to, width, height, direction }) => {
+            (gradient(from, to, width, height, direction), outfile)
+        }
+        Some(Commands::Noise { outfile, kind, seed, width, height }) => (noise(kind, seed, width, height), outfile),
+        Some(Commands::Plasma { outfile, scale, seed, width, height }) => {
+            (plasma(scale, seed, width, height), outfile)
+        }
+        Some(Commands::Solid { outfile, color, width, height }) => (solid(color, width, height), outfile),
+        Some(Commands::Checkerboard { outfile, cell_size, width, height, color1, color2 }) => {
+            (checkerboard(cell_size, width, height, color1, color2), outfile)
+        }
+        _ => print_usage_and_exit(),
+    };
+    check_cancelled()?;
+    save_image(process_image(img, &cli), &outfile, SaveOptions::from_cli(&cli))
 }
 
-fn blur(img: DynamicImage, sigma: f32) -> DynamicImage {
-    img.blur(sigma)
+// Generated images are left unfinished once cancelled, so they are not saved.
+fn check_cancelled() -> Result<(), ProcessError> {
+    if progress::is_cancelled() {
+        return Err(ProcessError::Interrupted("nothing was written".to_string()));
+    }
+    Ok(())
 }
 
-fn brighten(img: DynamicImage, value: i32) -> DynamicImage {
-    img.brighten(value)
+fn process_image(img: DynamicImage, cli: &Cli) -> DynamicImage {
+    match cli.transforms.region {
+        Some(region) => apply_pipeline_in_region(img, &build_operations(cli), region),
+        None => apply_pipeline(img, &build_operations(cli)),
+    }
 }
 
-fn crop(mut img: DynamicImage, x: u32, y: u32, width: u32, height: u32) -> DynamicImage {
-    img.crop(x, y, width, height)
+// Lists the operations the transform flags ask for, in the order they are applied.
+fn build_operations(cli: &Cli) -> Vec<Operation> {
+    let args = &cli.transforms;
+    let mut ops = args.preset.as_ref().map(|preset| preset.operations.clone()).unwrap_or_default();
+    match &args.pipeline {
+        Some(pipeline) => ops.extend(pipeline.iter().cloned()),
+        None => ops.extend(transform_operations(args)),
+    }
+
+    if let Some(target) = args.convert_to {
+        ops.push(Operation::ConvertColor(target));
+    }
+
+    ops
 }
 
-fn rotate(img: DynamicImage, value: i32) -> DynamicImage {
-    match value {
-        90 => img.rotate90(),
-        180 => img.rotate180(),
-        270 => img.rotate270(),
-        _ => img,
+// The operations selected by the individual transform flags, in the order
+// they are applied.
+fn transform_operations(args: &TransformArgs) -> Vec<Operation> {
+    let mut ops = Vec::new();
+
+    // Trimming goes first so nothing else works on the border, then padding
+    // so the filters below see the extended edges.
+    if args.trim {
+        ops.push(Operation::Trim { color: args.trim_color, fuzz: args.fuzz });
+    }
+    if let Some((width, mode, fill)) = args.pad {
+        ops.push(Operation::Pad { width, mode, fill });
+    }
+
+    if let Some(sigma) = args.blur {
+        ops.push(Operation::Blur(sigma));
+    }
+
+    if let Some(radius) = args.fast_blur {
+        ops.push(Operation::FastBlur(radius));
+    }
+
+    if let Some(radius) = args.median {
+        ops.push(Operation::Median(radius));
+    }
+
+    if let Some(block) = args.pixelate {
+        ops.push(Operation::Pixelate { block, region: args.pixelate_region });
+    }
+
+    if let Some((sigma, threshold)) = args.sharpen {
+        ops.push(Operation::Sharpen { sigma, threshold });
+    }
+
+    if let Some(kernel) = args.convolve {
+        ops.push(Operation::Convolve(kernel));
+    }
+
+    if args.emboss {
+        ops.push(Operation::Emboss);
+    }
+
+    if let Some(value) = args.brighten {
+        ops.push(Operation::Brighten(value));
+    }
+
+    if let Some((r, g, b)) = args.levels {
+        ops.push(Operation::Levels { r, g, b });
+    }
+
+    if let Some(value) = args.contrast {
+        ops.push(Operation::Contrast(value));
+    }
+
+    if args.auto_contrast {
+        ops.push(Operation::AutoContrast(args.clip));
+    }
+
+    if let Some(g) = args.gamma {
+        ops.push(Operation::Gamma(g as f32));
+    }
+
+    if let Some(lut) = &args.lut {
+        ops.push(Operation::Lut(Box::new(lut.clone())));
+    }
+
+    if let Some(degrees) = args.huerotate {
+        ops.push(Operation::HueRotate(degrees));
+    }
+
+    if let Some(offset) = args.temperature {
+        ops.push(Operation::Temperature(offset));
+    }
+
+    if let Some((hue, tolerance)) = args.keep_hue {
+        ops.push(Operation::KeepHue { hue, tolerance });
+    }
+
+    if let Some(factor) = args.saturate {
+        ops.push(Operation::Saturate(factor));
+    }
+
+    if let Some((width, height)) = args.resize {
+        ops.push(Operation::Resize { width, height, keep_aspect: args.keep_aspect });
+    }
+
+    if let Some((width, height)) = args.pixel_resize {
+        ops.push(Operation::PixelResize { width, height });
+    }
+
+    if let Some((width, height)) = args.thumbnail {
+        ops.push(Operation::Thumbnail { width, height });
+    }
+
+    if let Some((width, height)) = args.seam_carve {
+        ops.push(Operation::SeamCarve { width, height });
+    }
+
+    if let Some(factor) = args.scale {
+        ops.push(Operation::Scale(factor as f32));
+    }
+
+    if let Some((x, y, width, height)) = args.crop.or(args.geometry) {
+        ops.push(Operation::Crop { x, y, width, height });
+    }
+
+    if let Some((x, y, width, height)) = args.crop_pct {
+        ops.push(Operation::CropPct { x, y, width, height });
+    }
+
+    if let Some((width, height)) = args.crop_aspect {
+        ops.push(Operation::CropAspect { width, height });
+    }
+
+    if let Some((width, color)) = args.border {
+        ops.push(Operation::Border { width, color });
+    }
+
+    if let Some(value) = args.rotate {
+        ops.push(Operation::Rotate(value));
+    }
+
+    if let Some(degrees) = args.rotate_deg {
+        ops.push(Operation::RotateDeg { degrees, fill: args.fill, expand: args.expand });
+    }
+
+    if args.deskew {
+        ops.push(Operation::Deskew(args.deskew_max_angle as f32));
+    }
+
+    if args.fliph {
+        ops.push(Operation::FlipH);
+    }
+
+    if args.flipv {
+        ops.push(Operation::FlipV);
+    }
+
+    if args.invert {
+        ops.push(Operation::Invert);
+    }
+
+    if args.grayscale {
+        ops.push(Operation::Grayscale);
+    }
+
+    if args.sepia {
+        ops.push(Operation::Sepia);
+    }
+
+    if let Some(order) = args.swap_channels {
+        ops.push(Operation::SwapChannels(order));
+    }
+
+    if let Some(channel) = args.extract_channel {
+        ops.push(Operation::ExtractChannel(channel));
+    }
+
+    if let Some(levels) = args.posterize {
+        ops.push(Operation::Posterize { levels, dither: args.dither });
+    }
+
+    if let Some(colors) = args.palette {
+        ops.push(Operation::Quantize { colors, dither: args.dither });
+    }
+
+    if let Some(value) = args.threshold {
+        ops.push(Operation::Threshold(value));
+    }
+
+    if args.edges {
+        ops.push(Operation::Edges);
+    }
+
+    if let Some((sigma1, sigma2)) = args.dog {
+        ops.push(Operation::DifferenceOfGaussians { sigma1, sigma2, normalize: args.dog_normalize });
+    }
+
+    if let Some(strength) = args.vignette {
+        ops.push(Operation::Vignette(strength));
+    }
+
+    if let Some((threshold, intensity)) = args.bloom {
+        ops.push(Operation::Bloom { threshold, intensity });
+    }
+    if let Some((radius, intensity)) = args.oil {
+        ops.push(Operation::Oil { radius, intensity });
+    }
+
+    if let Some((r, g, b, tolerance)) = args.colorkey {
+        ops.push(Operation::ColorKey { r, g, b, tolerance });
+    }
+
+    if args.circle {
+        ops.push(Operation::Circle);
     }
+
+    if let Some(radius) = args.round_corners {
+        ops.push(Operation::RoundCorners(radius));
+    }
+
+    ops
 }
 
-fn invert(mut img: DynamicImage) -> DynamicImage {
-    img.invert();
-    img
+fn print_formats() {
+    println!("{:<10} {:<6} {:<6} extensions", "format", "read", "write");
+    for format in ImageFormat::all() {
+        let yes_no = |enabled: bool| if enabled { "yes" } else { "no" };
+        println!(
+            "{:<10} {:<6} {:<6} {}",
+            format!("{:?}", format),
+            yes_no(format.reading_enabled()),
+            yes_no(format.writing_enabled()),
+            format.extensions_str().join(", ")
+        );
+    }
 }
 
-fn grayscale(img: DynamicImage) -> DynamicImage {
-    img.grayscale()
+fn print_usage_and_exit() -> ! {
+    Cli::command().print_help().unwrap();
+    exit(-1);
 }
 
-fn generate() -> DynamicImage {
-    let width = 800;
-    let height = 800;
+fn load_image(infile: &str, auto_orient: bool) -> Result<DynamicImage, ProcessError> {
+    timing::time(|| format!("load {}", infile), || read_image(infile, auto_orient))
+}
 
-    let mut imgbuf = image::ImageBuffer::new(width, height);
+fn read_image(infile: &str, auto_orient: bool) -> Result<DynamicImage, ProcessError> {
+    if let Some(bytes) = load_image_source(infile)? {
+        if let Ok(format) = image::guess_format(&bytes) {
+            check_input_format(infile, format)?;
+        }
+        let img = image::load_from_memory(&bytes).map_err(ProcessError::Load)?;
+        if auto_orient {
+            return Ok(orient(img, read_orientation(&mut Cursor::new(&bytes))));
+        }
+        return Ok(img);
+    }
+    if let Ok(format) = ImageFormat::from_path(infile) {
+        check_input_format(infile, format)?;
+    }
+    let img = image::open(infile).map_err(ProcessError::Load)?;
+    if auto_orient {
+        let file = fs::File::open(infile).map_err(|err| ProcessError::Load(ImageError::IoError(err)))?;
+        return Ok(orient(img, read_orientation(&mut BufReader::new(file))));
+    }
+    Ok(img)
+}
 
-    for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-        let red = (0.5 * (x as f32 * 0.01).sin() * 255.0) as u8;
-        let green = (0.5 * (y as f32 * 0.01).sin() * 255.0) as u8;
-        let blue = (0.5 * (x as f32 * 0.01 + y as f32 * 0.01).sin() * 255.0) as u8;
+// Reads the bytes of an INFILE that isn't a local file: stdin ("-") or an
+// http(s) URL. Returns None for paths, which are opened by the image crate so
+// the extension can help pick the format.
+fn load_image_source(src: &str) -> Result<Option<Vec<u8>>, ProcessError> {
+    if src == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|err| ProcessError::Load(ImageError::IoError(err)))?;
+        Ok(Some(bytes))
+    } else if is_url(src) {
+        fetch(src).map(Some).map_err(|err| ProcessError::Fetch(src.to_string(), err))
+    } else {
+        Ok(None)
+    }
+}
 
-        *pixel = image::Rgb([red, green, blue]);
+fn check_input_format(infile: &str, format: ImageFormat) -> Result<(), ProcessError> {
+    if format.reading_enabled() {
+        return Ok(());
     }
+    Err(ProcessError::UnsupportedFormat(format!(
+        "{} ({:?} decoding is not enabled in this build, see --list-formats)",
+        infile, format
+    )))
+}
 
-    DynamicImage::ImageRgb8(imgbuf)
+// Reads the EXIF Orientation tag, treating missing or unreadable EXIF data as
+// already upright (orientation 1).
+fn read_orientation<R: BufRead + Seek>(reader: &mut R) -> u32 {
+    exif::Reader::new()
+        .read_from_container(reader)
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
 }
 
-fn fractal() -> DynamicImage {
-    let width = 800;
-    let height = 800;
-    let mut imgbuf = image::ImageBuffer::new(width, height);
-    let scale_x = 3.0 / width as f32;
-    let scale_y = 3.0 / height as f32;
+// Outfiles without an extension are written in the --format, the format of
+// the input or PNG, in that order, and get the matching extension.
+fn save_image(img: DynamicImage, outfile: &str, options: SaveOptions) -> Result<(), ProcessError> {
+    timing::time(|| format!("save {}", outfile), || write_image(img, outfile, options))
+}
 
-    for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-        let red = (0.3 * x as f32) as u8;
-        let blue = (0.3 * y as f32) as u8;
+fn write_image(img: DynamicImage, outfile: &str, options: SaveOptions) -> Result<(), ProcessError> {
+    let fallback = options.format.or(options.input_format).filter(ImageFormat::writing_enabled);
+    let outfile = &*with_extension(outfile, fallback.unwrap_or(ImageFormat::Png));
+    let format = if outfile == "-" {
+        options.format.ok_or_else(|| {
+            ProcessError::UnsupportedFormat("- (pass --format when writing to stdout)".to_string())
+        })?
+    } else {
+        validate_output_format(outfile)?
+    };
+    check_encoder(outfile, format, options.quality, options.lossless)?;
+    let img = match options.flatten {
+        Some(bg @ [r, g, b]) if img.color().has_alpha() && !stores_alpha(format) => {
+            log::warn(&format!("{} can't store transparency, flattening onto {},{},{}", outfile, r, g, b));
+            flatten(img, bg)
+        }
+        _ => img,
+    };
+    if options.print_hash {
+        eprintln!("sha256:{}", hash_image(&img));
+    }
+    if outfile == "-" {
+        let mut bytes = Cursor::new(Vec::new());
+        encode_image(&img, &mut bytes, format, &options).map_err(ProcessError::Save)?;
+        return io::stdout()
+            .write_all(bytes.get_ref())
+            .map_err(|err| ProcessError::Save(ImageError::IoError(err)));
+    }
+    let file = fs::File::create(outfile).map_err(|err| ProcessError::Save(ImageError::IoError(err)))?;
+    let mut writer = BufWriter::new(file);
+    encode_image(&img, &mut writer, format, &options).map_err(ProcessError::Save)
+}
 
-        let cx = y as f32 * scale_x - 1.5;
-        let cy = x as f32 * scale_y - 1.5;
+fn with_extension(outfile: &str, format: ImageFormat) -> Cow<'_, str> {
+    if outfile == "-" || Path::new(outfile).extension().is_some() {
+        Cow::Borrowed(outfile)
+    } else {
+        Cow::Owned(format!("{}.{}", outfile, format.extensions_str()[0]))
+    }
+}
 
-        let c = num_complex::Complex::new(-0.4, 0.6);
-        let mut z = num_complex::Complex::new(cx, cy);
+fn stores_alpha(format: ImageFormat) -> bool {
+    !matches!(format, ImageFormat::Jpeg | ImageFormat::Pnm | ImageFormat::Hdr)
+}
 
-        let mut green = 0;
-        while green < 255 && z.norm() <= 2.0 {
-            z = z * z + c;
-            green += 1;
+fn encode_image<W: Write + Seek>(
+    img: &DynamicImage,
+    writer: &mut W,
+    format: ImageFormat,
+    options: &SaveOptions,
+) -> ImageResult<()> {
+    match (format, options.quality) {
+        (ImageFormat::Jpeg, Some(quality)) => JpegEncoder::new_with_quality(writer, quality).encode_image(img),
+        (ImageFormat::WebP, quality) => encode_webp(img, writer, quality, options.lossless),
+        #[cfg(feature = "avif")]
+        (ImageFormat::Avif, Some(quality)) => {
+            let buf = img.to_rgba8();
+            AvifEncoder::new_with_speed_quality(writer, 4, quality).write_image(
+                &buf,
+                buf.width(),
+                buf.height(),
+                ColorType::Rgba8,
+            )
         }
+        _ => img.write_to(writer, format),
+    }
+}
 
-        *pixel = image::Rgb([red, green, blue]);
+// Lossy at `quality` (80 by default) when libwebp is compiled in, lossless
+// otherwise or with --lossless.
+fn encode_webp<W: Write>(img: &DynamicImage, writer: W, quality: Option<u8>, lossless: bool) -> ImageResult<()> {
+    let (buf, color) = if img.color().has_alpha() {
+        (img.to_rgba8().into_raw(), ColorType::Rgba8)
+    } else {
+        (img.to_rgb8().into_raw(), ColorType::Rgb8)
+    };
+    #[cfg(feature = "webp-lossy")]
+    if !lossless {
+        // Lossy encoding is deprecated in image until it has a pure Rust encoder.
+        #[allow(deprecated)]
+        let encoder =
+            WebPEncoder::new_with_quality(writer, WebPQuality::lossy(quality.unwrap_or(WebPQuality::DEFAULT)));
+        return encoder.encode(&buf, img.width(), img.height(), color);
     }
+    #[cfg(not(feature = "webp-lossy"))]
+    let _ = (quality, lossless);
+    WebPEncoder::new_lossless(writer).encode(&buf, img.width(), img.height(), color)
+}
 
-    DynamicImage::ImageRgb8(imgbuf)
+// Rejects encoder options that this build can't honor before any work is done.
+fn check_encoder(outfile: &str, format: ImageFormat, quality: Option<u8>, lossless: bool) -> Result<(), ProcessError> {
+    if format == ImageFormat::WebP && quality.is_some() && !lossless && !cfg!(feature = "webp-lossy") {
+        return Err(ProcessError::UnsupportedFormat(format!(
+            "{} (lossy WebP was not compiled in, rebuild with --features webp-lossy or pass --lossless)",
+            outfile
+        )));
+    }
+    Ok(())
 }
 
-fn parse_crop(s: &str) -> Result<(u32, u32, u32, u32), String> {
-    let parts: Vec<&str> = s.split(',').collect();
-    if parts.len() != 4 {
-        return Err(format!("Invalid crop value: {}", s));
+fn validate_output_format(outfile: &str) -> Result<ImageFormat, ProcessError> {
+    let format = Path::new(outfile).extension().and_then(ImageFormat::from_extension);
+    if format == Some(ImageFormat::Avif) && !ImageFormat::Avif.writing_enabled() {
+        return Err(ProcessError::UnsupportedFormat(format!(
+            "{} (AVIF was not compiled in, rebuild with --features avif)",
+            outfile
+        )));
     }
-    let x = parts[0].parse().map_err(|_| format!("Invalid x value: {}", parts[0]))?;
-    let y = parts[1].parse().map_err(|_| format!("Invalid y value: {}", parts[1]))?;
-    let width = parts[2].parse().map_err(|_| format!("Invalid width value: {}", parts[2]))?;
-    let height = parts[3].parse().map_err(|_| format!("Invalid height value: {}", parts[3]))?;
-    Ok((x, y, width, height))
+    format
+        .filter(ImageFormat::writing_enabled)
+        .ok_or_else(|| {
+            let supported: Vec<&str> = ImageFormat::all()
+                .filter(ImageFormat::writing_enabled)
+                .flat_map(ImageFormat::extensions_str)
+                .copied()
+                .collect();
+            ProcessError::UnsupportedFormat(format!(
+                "{} (supported extensions: {})",
+                outfile,
+                supported.join(", ")
+            ))
+        })
+}
+
+fn save_animation(frames: Vec<DynamicImage>, outfile: &str, fps: u32) -> Result<(), ProcessError> {
+    let file = fs::File::create(outfile).map_err(ProcessError::Io)?;
+    let delay = Delay::from_numer_denom_ms(1000, fps.max(1));
+
+    let mut encoder = GifEncoder::new_with_speed(file, 10);
+    encoder.set_repeat(Repeat::Infinite).map_err(ProcessError::Save)?;
+    encoder
+        .encode_frames(frames.into_iter().map(|img| Frame::from_parts(img.to_rgba8(), 0, 0, delay)))
+        .map_err(ProcessError::Save)
 }
@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+use crate::transform::PadMode;
+
+pub fn parse_crop(s: &str) -> Result<(u32, u32, u32, u32), String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 4 {
+        return Err(format!("Invalid crop value: {}", s));
+    }
+    let x = parts[0].parse().map_err(|_| format!("Invalid x value: {}", parts[0]))?;
+    let y = parts[1].parse().map_err(|_| format!("Invalid y value: {}", parts[1]))?;
+    let width = parts[2].parse().map_err(|_| format!("Invalid width value: {}", parts[2]))?;
+    let height = parts[3].parse().map_err(|_| format!("Invalid height value: {}", parts[3]))?;
+    Ok((x, y, width, height))
+}
+
+// Parses ImageMagick style `WxH+X+Y` geometry into the same (x, y, width,
+// height) as `parse_crop`. The offsets may be left out and default to 0.
+pub fn parse_geometry(s: &str) -> Result<(u32, u32, u32, u32), String> {
+    let (size, offsets) = s.split_once('+').unwrap_or((s, "0+0"));
+    let (width, height) = parse_dimensions(size).map_err(|_| format!("Invalid geometry: {} (expected WxH+X+Y)", s))?;
+    let (x, y) = offsets
+        .split_once('+')
+        .ok_or_else(|| format!("Invalid geometry: {} (expected WxH+X+Y)", s))?;
+    let x = x.parse().map_err(|_| format!("Invalid x offset: {}", x))?;
+    let y = y.parse().map_err(|_| format!("Invalid y offset: {}", y))?;
+    Ok((x, y, width, height))
+}
+
+pub fn parse_dimensions(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("Invalid dimensions: {} (expected WIDTHxHEIGHT)", s))?;
+    let width: u32 = width.parse().map_err(|_| format!("Invalid width value: {}", width))?;
+    let height: u32 = height.parse().map_err(|_| format!("Invalid height value: {}", height))?;
+    if width == 0 || height == 0 {
+        return Err(format!("Width and height must be greater than zero: {}", s));
+    }
+    Ok((width, height))
+}
+
+// Parses an aspect ratio such as `16:9`.
+pub fn parse_aspect(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid aspect ratio: {} (expected W:H)", s))?;
+    let parse = |part: &str| match part.trim().parse::<i64>() {
+        Ok(value) if value > 0 && value <= u32::MAX as i64 => Ok(value as u32),
+        Ok(_) => Err(format!("Aspect ratio components must be greater than zero: {}", s)),
+        Err(_) => Err(format!("Invalid aspect ratio: {} (expected W:H)", s)),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+pub fn parse_point(s: &str) -> Result<(f64, f64), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid point: {} (expected X,Y)", s))?;
+    let x = x.parse().map_err(|_| format!("Invalid x value: {}", x))?;
+    let y = y.parse().map_err(|_| format!("Invalid y value: {}", y))?;
+    Ok((x, y))
+}
+
+pub fn parse_sharpen(s: &str) -> Result<(f32, i32), String> {
+    let (sigma, threshold) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid sharpen value: {} (expected SIGMA,THRESHOLD)", s))?;
+    let sigma = sigma.parse().map_err(|_| format!("Invalid sigma value: {}", sigma))?;
+    let threshold = threshold.parse().map_err(|_| format!("Invalid threshold value: {}", threshold))?;
+    Ok((sigma, threshold))
+}
+
+pub fn parse_bloom(s: &str) -> Result<(u8, f32), String> {
+    let (threshold, intensity) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid bloom value: {} (expected THRESHOLD,INTENSITY)", s))?;
+    let threshold = threshold
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid threshold value: {} (must be 0-255)", threshold))?;
+    let intensity: f32 = intensity.trim().parse().map_err(|_| format!("Invalid intensity value: {}", intensity))?;
+    if !(intensity >= 0.0 && intensity.is_finite()) {
+        return Err(format!("Intensity must not be negative: {}", intensity));
+    }
+    Ok((threshold, intensity))
+}
+
+// Parses `RADIUS,INTENSITY` for the oil painting filter, INTENSITY being the
+// number of brightness levels (1-256).
+pub fn parse_oil(s: &str) -> Result<(u32, u32), String> {
+    let (radius, intensity) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid oil value: {} (expected RADIUS,INTENSITY)", s))?;
+    let radius = radius.trim().parse().map_err(|_| format!("Invalid radius value: {}", radius))?;
+    match intensity.trim().parse() {
+        Ok(intensity @ 1..=256) => Ok((radius, intensity)),
+        _ => Err(format!("Invalid intensity value: {} (must be 1-256)", intensity)),
+    }
+}
+
+pub fn parse_border(s: &str) -> Result<(u32, [u8; 3]), String> {
+    let (width, color) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid border value: {} (expected WIDTH,R,G,B)", s))?;
+    let width = width.trim().parse().map_err(|_| format!("Invalid border width: {}", width))?;
+    Ok((width, parse_rgb(color)?))
+}
+
+// Parses `N,MODE` or `N,constant,R,G,B`, the fill color defaults to black.
+pub fn parse_pad(s: &str) -> Result<(u32, PadMode, [u8; 3]), String> {
+    let mut parts = s.splitn(3, ',');
+    let (Some(n), Some(mode)) = (parts.next(), parts.next()) else {
+        return Err(format!("Invalid pad value: {} (expected N,MODE)", s));
+    };
+    let n = n.trim().parse().map_err(|_| format!("Invalid pad width: {}", n))?;
+    let mode = PadMode::from_str(mode.trim(), true)
+        .map_err(|_| format!("Invalid pad mode: {} (expected mirror, edge or constant)", mode))?;
+    match (mode, parts.next()) {
+        (_, None) => Ok((n, mode, [0, 0, 0])),
+        (PadMode::Constant, Some(fill)) => Ok((n, mode, parse_rgb(fill)?)),
+        (_, Some(_)) => Err(format!("Only constant padding takes a color: {}", s)),
+    }
+}
+
+pub fn parse_dog(s: &str) -> Result<(f32, f32), String> {
+    let (sigma1, sigma2) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid dog value: {} (expected SIGMA1,SIGMA2)", s))?;
+    let sigma1 = parse_positive(sigma1.trim())? as f32;
+    let sigma2 = parse_positive(sigma2.trim())? as f32;
+    Ok((sigma1, sigma2))
+}
+
+// Parses `HUE,TOLERANCE` in degrees, the tolerance can't be negative.
+pub fn parse_keep_hue(s: &str) -> Result<(f32, f32), String> {
+    let (hue, tolerance) = s
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid keep-hue value: {} (expected HUE,TOLERANCE)", s))?;
+    let hue: f32 = hue.trim().parse().map_err(|_| format!("Invalid hue value: {}", hue))?;
+    let tolerance: f32 = tolerance.trim().parse().map_err(|_| format!("Invalid tolerance value: {}", tolerance))?;
+    if !(tolerance >= 0.0 && hue.is_finite() && tolerance.is_finite()) {
+        return Err(format!("Hue must be a number and tolerance must not be negative: {}", s));
+    }
+    Ok((hue, tolerance))
+}
+
+// A color distance for --fuzz, 0 only matches the exact color.
+pub fn parse_fuzz(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(fuzz) if fuzz >= 0.0 && fuzz.is_finite() => Ok(fuzz),
+        _ => Err(format!("Invalid fuzz value: {} (must be a number >= 0)", s)),
+    }
+}
+
+pub fn parse_rgb(s: &str) -> Result<[u8; 3], String> {
+    parse_channels(s, "R,G,B")
+}
+
+pub fn parse_rgba(s: &str) -> Result<[u8; 4], String> {
+    parse_channels(s, "R,G,B,A")
+}
+
+fn parse_channels<const N: usize>(s: &str, expected: &str) -> Result<[u8; N], String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != N {
+        return Err(format!("Invalid color value: {} (expected {})", s, expected));
+    }
+    let mut color = [0; N];
+    for (channel, part) in color.iter_mut().zip(parts) {
+        *channel = part
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid color component: {} (must be 0-255)", part))?;
+    }
+    Ok(color)
+}
+
+pub fn parse_colorkey(s: &str) -> Result<(u8, u8, u8, f32), String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 4 {
+        return Err(format!("Invalid colorkey value: {} (expected R,G,B,TOLERANCE)", s));
+    }
+    let [r, g, b] = parse_rgb(&parts[..3].join(","))?;
+    let tolerance: f32 = parts[3].trim().parse().map_err(|_| format!("Invalid tolerance value: {}", parts[3]))?;
+    if tolerance < 0.0 {
+        return Err(format!("Tolerance must not be negative: {}", parts[3]));
+    }
+    Ok((r, g, b, tolerance))
+}
+
+// Parses a permutation of "rgb" such as "bgr" into the source channel of each
+// output channel.
+pub fn parse_channel_order(s: &str) -> Result<[usize; 3], String> {
+    let mut order = [0; 3];
+    let mut seen = [false; 3];
+    let letters: Vec<char> = s.trim().to_ascii_lowercase().chars().collect();
+    if letters.len() != 3 {
+        return Err(format!("Invalid channel order: {} (expected a permutation of rgb)", s));
+    }
+    for (slot, letter) in order.iter_mut().zip(letters) {
+        let channel = "rgb"
+            .find(letter)
+            .ok_or_else(|| format!("Invalid channel order: {} (expected a permutation of rgb)", s))?;
+        if seen[channel] {
+            return Err(format!("Invalid channel order: {} (each of r, g and b must appear once)", s));
+        }
+        seen[channel] = true;
+        *slot = channel;
+    }
+    Ok(order)
+}
+
+pub fn parse_opacity(s: &str) -> Result<f32, String> {
+    let opacity: f32 = s.parse().map_err(|_| format!("Invalid opacity value: {}", s))?;
+    if !(0.0..=1.0).contains(&opacity) {
+        return Err(format!("Opacity must be between 0.0 and 1.0: {}", s));
+    }
+    Ok(opacity)
+}
+
+pub fn parse_fraction(s: &str) -> Result<f32, String> {
+    let value: f32 = s.parse().map_err(|_| format!("Invalid number: {}", s))?;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(format!("Value must be between 0.0 and 1.0: {}", s));
+    }
+    Ok(value)
+}
+
+// A percentage from 0 to 100, with or without a trailing '%'.
+pub fn parse_percent(s: &str) -> Result<f32, String> {
+    let value: f32 = s.trim().trim_end_matches('%').parse().map_err(|_| format!("Invalid percentage: {}", s))?;
+    if !(0.0..=100.0).contains(&value) {
+        return Err(format!("Percentage must be between 0 and 100: {}", s));
+    }
+    Ok(value)
+}
+
+// A white balance shift from -100 (coolest) to 100 (warmest).
+pub fn parse_temperature(s: &str) -> Result<i32, String> {
+    let value: i32 = s.trim().parse().map_err(|_| format!("Invalid temperature: {}", s))?;
+    if !(-100..=100).contains(&value) {
+        return Err(format!("Temperature must be between -100 and 100: {}", s));
+    }
+    Ok(value)
+}
+
+pub fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+        Ok(_) => Err(format!("Value must be greater than 0: {}", s)),
+        Err(_) => Err(format!("Invalid number: {}", s)),
+    }
+}
+
+pub fn parse_levels(s: &str) -> Result<(i32, i32, i32), String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 3 {
+        return Err(format!("Invalid levels value: {} (expected R,G,B)", s));
+    }
+    let r = parts[0].parse().map_err(|_| format!("Invalid red value: {}", parts[0]))?;
+    let g = parts[1].parse().map_err(|_| format!("Invalid green value: {}", parts[1]))?;
+    let b = parts[2].parse().map_err(|_| format!("Invalid blue value: {}", parts[2]))?;
+    Ok((r, g, b))
+}
+
+pub fn parse_crop_pct(s: &str) -> Result<(f32, f32, f32, f32), String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 4 {
+        return Err(format!("Invalid crop percentage value: {} (expected X%,Y%,W%,H%)", s));
+    }
+    let mut values = [0.0; 4];
+    for (value, part) in values.iter_mut().zip(&parts) {
+        let number = part.trim().trim_end_matches('%');
+        *value = number.parse().map_err(|_| format!("Invalid percentage: {}", part))?;
+        if !(0.0..=100.0).contains(value) {
+            return Err(format!("Percentage must be between 0 and 100: {}", part));
+        }
+    }
+    let [x, y, width, height] = values;
+    if width == 0.0 || height == 0.0 {
+        return Err(format!("Crop width and height must be greater than 0%: {}", s));
+    }
+    if x + width > 100.0 || y + height > 100.0 {
+        return Err(format!("Crop region extends past the image: {}", s));
+    }
+    Ok((x, y, width, height))
+}
+
+pub fn parse_kernel(s: &str) -> Result<[f32; 9], String> {
+    let rows: Vec<&str> = s.split(';').collect();
+    if rows.len() != 3 {
+        return Err(format!("Invalid kernel: {} (expected 3 rows separated by ';')", s));
+    }
+    let mut kernel = [0.0; 9];
+    for (i, row) in rows.iter().enumerate() {
+        let values: Vec<&str> = row.split(',').collect();
+        if values.len() != 3 {
+            return Err(format!("Invalid kernel row: {} (kernel must be 3x3)", row));
+        }
+        for (j, value) in values.iter().enumerate() {
+            kernel[i * 3 + j] = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid kernel value: {}", value))?;
+        }
+    }
+    Ok(kernel)
+}
+
+// Parses a batch manifest with one `INFILE<TAB>OUTFILE` pair per line. Blank
+// lines and lines starting with '#' are skipped.
+pub fn parse_manifest(s: &str) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut pairs = Vec::new();
+    for (number, line) in (1..).zip(s.lines()) {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        match line.split('\t').collect::<Vec<_>>()[..] {
+            [infile, outfile] if !infile.is_empty() && !outfile.is_empty() => {
+                pairs.push((PathBuf::from(infile), PathBuf::from(outfile)));
+            }
+            _ => return Err(format!("line {}: expected INFILE<TAB>OUTFILE, got {:?}", number, line)),
+        }
+    }
+    Ok(pairs)
+}
@@ -0,0 +1,523 @@
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use image::{imageops, DynamicImage};
+
+use crate::parse::{
+    parse_aspect, parse_bloom, parse_border, parse_channel_order, parse_colorkey, parse_crop,
+    parse_crop_pct, parse_dimensions, parse_dog, parse_fraction, parse_fuzz, parse_geometry,
+    parse_pad, parse_percent, parse_positive, parse_keep_hue, parse_kernel, parse_levels, parse_oil,
+    parse_rgb, parse_sharpen, parse_temperature,
+};
+use crate::lut::{load_lut, Lut};
+use crate::timing::time;
+use crate::transform::{
+    apply_lut, auto_contrast, bloom, blur, border, brighten, circle_mask, colorkey, contrast,
+    convert_color, convolve, crop, crop_aspect, crop_pct, deskew, difference_of_gaussians, edges,
+    emboss, extract_channel, fast_blur, fliph, flipv, gamma, grayscale, huerotate, invert, keep_hue,
+    dither, levels, median, oil_paint, pad, pixel_resize, pixelate, posterize, quantize,
+    quantize_palette, resize, rotate, rotate_arbitrary, round_corners, saturate, scale, seam_carve,
+    sepia, sharpen, swap_channels, temperature, threshold, thumbnail, trim, vignette, Channel,
+    ColorTarget, Dither, PadMode, Palette, DESKEW_MAX_ANGLE,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Blur(f32),
+    FastBlur(u32),
+    Median(u32),
+    Pixelate { block: u32, region: Option<(u32, u32, u32, u32)> },
+    Sharpen { sigma: f32, threshold: i32 },
+    Convolve([f32; 9]),
+    Emboss,
+    Brighten(i32),
+    Levels { r: i32, g: i32, b: i32 },
+    Contrast(f32),
+    AutoContrast(f32),
+    Gamma(f32),
+    Lut(Box<Lut>),
+    HueRotate(i32),
+    Saturate(f32),
+    Temperature(i32),
+    KeepHue { hue: f32, tolerance: f32 },
+    Resize { width: u32, height: u32, keep_aspect: bool },
+    PixelResize { width: u32, height: u32 },
+    Thumbnail { width: u32, height: u32 },
+    SeamCarve { width: u32, height: u32 },
+    Scale(f32),
+    Crop { x: u32, y: u32, width: u32, height: u32 },
+    Trim { color: Option<[u8; 3]>, fuzz: f32 },
+    CropPct { x: f32, y: f32, width: f32, height: f32 },
+    CropAspect { width: u32, height: u32 },
+    Border { width: u32, color: [u8; 3] },
+    Pad { width: u32, mode: PadMode, fill: [u8; 3] },
+    Rotate(i32),
+    RotateDeg { degrees: f32, fill: [u8; 4], expand: bool },
+    Deskew(f32),
+    FlipH,
+    FlipV,
+    Invert,
+    Grayscale,
+    Sepia,
+    SwapChannels([usize; 3]),
+    ExtractChannel(Channel),
+    Posterize { levels: u16, dither: Dither },
+    Quantize { colors: u16, dither: Dither },
+    Threshold(u8),
+    Edges,
+    DifferenceOfGaussians { sigma1: f32, sigma2: f32, normalize: bool },
+    Vignette(f32),
+    Bloom { threshold: u8, intensity: f32 },
+    Oil { radius: u32, intensity: u32 },
+    ColorKey { r: u8, g: u8, b: u8, tolerance: f32 },
+    Circle,
+    RoundCorners(u32),
+    ConvertColor(ColorTarget),
+}
+
+impl Operation {
+    // Whether each output pixel depends only on the same input pixel, so the
+    // operation can be applied to an image a few rows at a time.
+    pub fn is_streamable(&self) -> bool {
+        matches!(
+            self,
+            Operation::Brighten(_)
+                | Operation::Levels { .. }
+                | Operation::Contrast(_)
+                | Operation::Gamma(_)
+                | Operation::Lut(_)
+                | Operation::HueRotate(_)
+                | Operation::Saturate(_)
+                | Operation::Temperature(_)
+                | Operation::KeepHue { .. }
+                | Operation::Invert
+                | Operation::Grayscale
+                | Operation::Sepia
+                | Operation::SwapChannels(_)
+                | Operation::ExtractChannel(_)
+                | Operation::Posterize { dither: Dither::None, .. }
+                | Operation::Threshold(_)
+                | Operation::ColorKey { .. }
+                | Operation::ConvertColor(_)
+        )
+    }
+
+    // Whether the output can have transparent pixels even if the input has none.
+    pub fn adds_transparency(&self) -> bool {
+        matches!(self, Operation::ColorKey { .. } | Operation::Circle | Operation::RoundCorners(_))
+    }
+
+    // Whether the operation works on 16-bit images directly rather than
+    // converting them to 8 bits per channel first.
+    pub fn keeps_depth(&self) -> bool {
+        matches!(
+            self,
+            Operation::Blur(_)
+                | Operation::Sharpen { .. }
+                | Operation::Convolve(_)
+                | Operation::Emboss
+                | Operation::Brighten(_)
+                | Operation::Contrast(_)
+                | Operation::HueRotate(_)
+                | Operation::Resize { .. }
+                | Operation::PixelResize { .. }
+                | Operation::Thumbnail { .. }
+                | Operation::Scale(_)
+                | Operation::Crop { .. }
+                | Operation::Trim { .. }
+                | Operation::CropPct { .. }
+                | Operation::Rotate(_)
+                | Operation::FlipH
+                | Operation::FlipV
+                | Operation::Invert
+                | Operation::Grayscale
+                | Operation::ConvertColor(_)
+        )
+    }
+
+    // Whether the output has the same width and height as the input.
+    pub fn keeps_dimensions(&self) -> bool {
+        match *self {
+            Operation::Resize { .. }
+            | Operation::PixelResize { .. }
+            | Operation::Thumbnail { .. }
+            | Operation::SeamCarve { .. }
+            | Operation::Scale(_)
+            | Operation::Crop { .. }
+            | Operation::Trim { .. }
+            | Operation::CropPct { .. }
+            | Operation::CropAspect { .. }
+            | Operation::Border { .. }
+            | Operation::Pad { .. }
+            | Operation::RotateDeg { expand: true, .. } => false,
+            Operation::Rotate(value) => value != 90 && value != 270,
+            _ => true,
+        }
+    }
+
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        match *self {
+            Operation::Blur(sigma) => blur(img, sigma),
+            Operation::FastBlur(radius) => fast_blur(img, radius),
+            Operation::Median(radius) => median(img, radius),
+            Operation::Pixelate { block, region } => pixelate(img, block, region),
+            Operation::Sharpen { sigma, threshold } => sharpen(img, sigma, threshold),
+            Operation::Convolve(kernel) => convolve(img, kernel),
+            Operation::Emboss => emboss(img),
+            Operation::Brighten(value) => brighten(img, value),
+            Operation::Levels { r, g, b } => levels(img, r, g, b),
+            Operation::Contrast(value) => contrast(img, value),
+            Operation::AutoContrast(clip) => auto_contrast(img, clip),
+            Operation::Gamma(g) => gamma(img, g),
+            Operation::Lut(ref lut) => apply_lut(img, &lut.table),
+            Operation::HueRotate(degrees) => huerotate(img, degrees),
+            Operation::Temperature(offset) => temperature(img, offset),
+            Operation::KeepHue { hue, tolerance } => keep_hue(img, hue, tolerance),
+            Operation::Saturate(factor) => saturate(img, factor),
+            Operation::Resize { width, height, keep_aspect } => resize(img, width, height, keep_aspect),
+            Operation::PixelResize { width, height } => pixel_resize(img, width, height),
+            Operation::Thumbnail { width, height } => thumbnail(img, width, height),
+            Operation::SeamCarve { width, height } => seam_carve(img, width, height),
+            Operation::Scale(factor) => scale(img, factor),
+            Operation::Crop { x, y, width, height } => crop(img, x, y, width, height),
+            Operation::Trim { color, fuzz } => trim(img, color, fuzz),
+            Operation::CropPct { x, y, width, height } => crop_pct(img, x, y, width, height),
+            Operation::CropAspect { width, height } => crop_aspect(img, width, height),
+            Operation::Border { width, color } => border(img, width, color),
+            Operation::Pad { width, mode, fill } => pad(img, width, mode, fill),
+            Operation::Rotate(value) => rotate(img, value),
+            Operation::RotateDeg { degrees, fill, expand } => rotate_arbitrary(img, degrees, fill, expand),
+            Operation::Deskew(max_angle) => deskew(img, max_angle),
+            Operation::FlipH => fliph(img),
+            Operation::FlipV => flipv(img),
+            Operation::Invert => invert(img),
+            Operation::Grayscale => grayscale(img),
+            Operation::Sepia => sepia(img),
+            Operation::SwapChannels(order) => swap_channels(img, order),
+            Operation::ExtractChannel(channel) => extract_channel(img, channel),
+            Operation::Posterize { levels, dither: Dither::None } => posterize(img, levels),
+            Operation::Posterize { levels, dither: method } => dither(img, method, &Palette::Levels(levels)),
+            Operation::Quantize { colors, dither: Dither::None } => quantize(img, colors),
+            Operation::Quantize { colors, dither: method } => {
+                let palette = Palette::Colors(quantize_palette(&img, colors));
+                dither(img, method, &palette)
+            }
+            Operation::Threshold(value) => threshold(img, value),
+            Operation::Edges => edges(img),
+            Operation::DifferenceOfGaussians { sigma1, sigma2, normalize } => {
+                difference_of_gaussians(img, sigma1, sigma2, normalize)
+            }
+            Operation::Vignette(strength) => vignette(img, strength),
+            Operation::Bloom { threshold, intensity } => bloom(img, threshold, intensity),
+            Operation::Oil { radius, intensity } => oil_paint(img, radius, intensity),
+            Operation::ColorKey { r, g, b, tolerance } => colorkey(img, r, g, b, tolerance),
+            Operation::Circle => circle_mask(img),
+            Operation::RoundCorners(radius) => round_corners(img, radius),
+            Operation::ConvertColor(target) => convert_color(img, target),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Blur(sigma) => write!(f, "blur({:?})", sigma),
+            Operation::FastBlur(radius) => write!(f, "fast_blur({})", radius),
+            Operation::Median(radius) => write!(f, "median({})", radius),
+            Operation::Pixelate { block, region: None } => write!(f, "pixelate({})", block),
+            Operation::Pixelate { block, region: Some((x, y, width, height)) } => {
+                write!(f, "pixelate({}, region {}, {}, {}, {})", block, x, y, width, height)
+            }
+            Operation::Sharpen { sigma, threshold } => write!(f, "sharpen({:?}, {})", sigma, threshold),
+            Operation::Convolve(kernel) => write!(f, "convolve({:?})", kernel),
+            Operation::Emboss => write!(f, "emboss"),
+            Operation::Brighten(value) => write!(f, "brighten({})", value),
+            Operation::Levels { r, g, b } => write!(f, "levels({}, {}, {})", r, g, b),
+            Operation::Contrast(value) => write!(f, "contrast({:?})", value),
+            Operation::AutoContrast(clip) => write!(f, "auto_contrast(clip {}%)", clip),
+            Operation::Gamma(g) => write!(f, "gamma({:?})", g),
+            Operation::Lut(lut) => write!(f, "lut({})", lut.path),
+            Operation::HueRotate(degrees) => write!(f, "huerotate({})", degrees),
+            Operation::Temperature(offset) => write!(f, "temperature({})", offset),
+            Operation::KeepHue { hue, tolerance } => write!(f, "keep_hue({:?}, {:?})", hue, tolerance),
+            Operation::Saturate(factor) => write!(f, "saturate({:?})", factor),
+            Operation::Resize { width, height, keep_aspect: false } => write!(f, "resize({}x{})", width, height),
+            Operation::Resize { width, height, keep_aspect: true } => {
+                write!(f, "resize({}x{}, keep_aspect)", width, height)
+            }
+            Operation::PixelResize { width, height } => write!(f, "pixel_resize({}x{})", width, height),
+            Operation::Thumbnail { width, height } => write!(f, "thumbnail({}x{})", width, height),
+            Operation::SeamCarve { width, height } => write!(f, "seam_carve({}x{})", width, height),
+            Operation::Scale(factor) => write!(f, "scale({:?})", factor),
+            Operation::Crop { x, y, width, height } => write!(f, "crop({}, {}, {}, {})", x, y, width, height),
+            Operation::Trim { color: Some([r, g, b]), fuzz } => write!(f, "trim({},{},{}, fuzz {:?})", r, g, b, fuzz),
+            Operation::Trim { color: None, fuzz } => write!(f, "trim(top-left, fuzz {:?})", fuzz),
+            Operation::CropPct { x, y, width, height } => {
+                write!(f, "crop_pct({}%, {}%, {}%, {}%)", x, y, width, height)
+            }
+            Operation::CropAspect { width, height } => write!(f, "crop_aspect({}:{})", width, height),
+            Operation::Border { width, color: [r, g, b] } => write!(f, "border({}, {},{},{})", width, r, g, b),
+            Operation::Pad { width, mode: PadMode::Constant, fill: [r, g, b] } => {
+                write!(f, "pad({}, constant, {},{},{})", width, r, g, b)
+            }
+            Operation::Pad { width, mode, .. } => write!(f, "pad({}, {})", width, mode),
+            Operation::Rotate(value) => write!(f, "rotate({})", value),
+            Operation::RotateDeg { degrees, fill: [r, g, b, a], expand } => {
+                write!(f, "rotate_deg({:?}, fill {},{},{},{}", degrees, r, g, b, a)?;
+                if *expand {
+                    write!(f, ", expand")?;
+                }
+                write!(f, ")")
+            }
+            Operation::Deskew(max_angle) => write!(f, "deskew(max {:?})", max_angle),
+            Operation::FlipH => write!(f, "fliph"),
+            Operation::FlipV => write!(f, "flipv"),
+            Operation::Invert => write!(f, "invert"),
+            Operation::Grayscale => write!(f, "grayscale"),
+            Operation::Sepia => write!(f, "sepia"),
+            Operation::SwapChannels(order) => {
+                let order: String = order.iter().map(|&channel| ['r', 'g', 'b'][channel]).collect();
+                write!(f, "swap_channels({})", order)
+            }
+            Operation::ExtractChannel(channel) => write!(f, "extract_channel({})", channel),
+            Operation::Posterize { levels, dither: Dither::None } => write!(f, "posterize({})", levels),
+            Operation::Posterize { levels, dither } => write!(f, "posterize({}, {} dither)", levels, dither),
+            Operation::Quantize { colors, dither: Dither::None } => write!(f, "palette({})", colors),
+            Operation::Quantize { colors, dither } => write!(f, "palette({}, {} dither)", colors, dither),
+            Operation::Threshold(value) => write!(f, "threshold({})", value),
+            Operation::Edges => write!(f, "edges"),
+            Operation::DifferenceOfGaussians { sigma1, sigma2, normalize } => {
+                write!(f, "dog({:?}, {:?}", sigma1, sigma2)?;
+                if *normalize {
+                    write!(f, ", normalize")?;
+                }
+                write!(f, ")")
+            }
+            Operation::Vignette(strength) => write!(f, "vignette({:?})", strength),
+            Operation::Bloom { threshold, intensity } => write!(f, "bloom({}, {:?})", threshold, intensity),
+            Operation::Oil { radius, intensity } => write!(f, "oil({}, {})", radius, intensity),
+            Operation::ColorKey { r, g, b, tolerance } => write!(f, "colorkey({}, {}, {}, {:?})", r, g, b, tolerance),
+            Operation::Circle => write!(f, "circle"),
+            Operation::RoundCorners(radius) => write!(f, "round_corners({})", radius),
+            Operation::ConvertColor(target) => write!(f, "as({})", target),
+        }
+    }
+}
+
+// Parses a single pipeline step such as `blur=2.0` or `grayscale`. Steps that
+// take several numbers separate them with ':' since ',' separates the steps.
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (s.trim(), None),
+        };
+
+        match name {
+            "blur" => Ok(Operation::Blur(parse_value(name, value)?)),
+            "fast-blur" => Ok(Operation::FastBlur(parse_value(name, value)?)),
+            "median" => Ok(Operation::Median(parse_value(name, value)?)),
+            // Either `pixelate=BLOCK` or `pixelate=BLOCK:X:Y:W:H`.
+            "pixelate" => {
+                let value = require_value(name, value)?;
+                let (block, region) = match value.split_once(':') {
+                    Some((block, region)) => (block, Some(parse_crop(&region.replace(':', ","))?)),
+                    None => (value, None),
+                };
+                match block.parse() {
+                    Ok(block) if block > 0 => Ok(Operation::Pixelate { block, region }),
+                    _ => Err(format!("Invalid pixelate block size: {}", block)),
+                }
+            }
+            "sharpen" => {
+                let (sigma, threshold) = parse_sharpen(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::Sharpen { sigma, threshold })
+            }
+            "convolve" => Ok(Operation::Convolve(parse_kernel(&require_value(name, value)?.replace(':', ","))?)),
+            "emboss" => Ok(Operation::Emboss),
+            "brighten" => Ok(Operation::Brighten(parse_value(name, value)?)),
+            "levels" => {
+                let (r, g, b) = parse_levels(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::Levels { r, g, b })
+            }
+            "contrast" => Ok(Operation::Contrast(parse_value(name, value)?)),
+            "auto-contrast" => Ok(Operation::AutoContrast(value.map(parse_percent).transpose()?.unwrap_or(0.0))),
+            "gamma" => Ok(Operation::Gamma(parse_positive(require_value(name, value)?)? as f32)),
+            "lut" => Ok(Operation::Lut(Box::new(load_lut(require_value(name, value)?)?))),
+            "huerotate" => Ok(Operation::HueRotate(parse_value(name, value)?)),
+            "temperature" => Ok(Operation::Temperature(parse_temperature(require_value(name, value)?)?)),
+            "keep-hue" => {
+                let (hue, tolerance) = parse_keep_hue(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::KeepHue { hue, tolerance })
+            }
+            "saturate" => Ok(Operation::Saturate(parse_value(name, value)?)),
+            "resize" | "resize-aspect" => {
+                let (width, height) = parse_dimensions(require_value(name, value)?)?;
+                Ok(Operation::Resize { width, height, keep_aspect: name == "resize-aspect" })
+            }
+            "pixel-resize" => {
+                let (width, height) = parse_dimensions(require_value(name, value)?)?;
+                Ok(Operation::PixelResize { width, height })
+            }
+            "thumbnail" => {
+                let (width, height) = parse_dimensions(require_value(name, value)?)?;
+                Ok(Operation::Thumbnail { width, height })
+            }
+            "seam-carve" => {
+                let (width, height) = parse_dimensions(require_value(name, value)?)?;
+                Ok(Operation::SeamCarve { width, height })
+            }
+            "scale" => Ok(Operation::Scale(parse_positive(require_value(name, value)?)? as f32)),
+            "crop" => {
+                let (x, y, width, height) = parse_crop(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::Crop { x, y, width, height })
+            }
+            // trim, trim=FUZZ or trim=FUZZ:R:G:B
+            "trim" => {
+                let (fuzz, color) = match value.map(|value| value.split_once(':').unwrap_or((value, ""))) {
+                    None => (0.0, None),
+                    Some((fuzz, "")) => (parse_fuzz(fuzz)?, None),
+                    Some((fuzz, color)) => (parse_fuzz(fuzz)?, Some(parse_rgb(&color.replace(':', ","))?)),
+                };
+                Ok(Operation::Trim { color, fuzz })
+            }
+            "geometry" => {
+                let (x, y, width, height) = parse_geometry(require_value(name, value)?)?;
+                Ok(Operation::Crop { x, y, width, height })
+            }
+            "crop-pct" => {
+                let (x, y, width, height) = parse_crop_pct(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::CropPct { x, y, width, height })
+            }
+            "crop-aspect" => {
+                let (width, height) = parse_aspect(require_value(name, value)?)?;
+                Ok(Operation::CropAspect { width, height })
+            }
+            "border" => {
+                let (width, color) = parse_border(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::Border { width, color })
+            }
+            "pad" => {
+                let (width, mode, fill) = parse_pad(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::Pad { width, mode, fill })
+            }
+            "rotate" => Ok(Operation::Rotate(parse_value(name, value)?)),
+            // Pipeline steps always fill the corners with transparency.
+            "rotate-deg" | "rotate-deg-expand" => Ok(Operation::RotateDeg {
+                degrees: parse_value(name, value)?,
+                fill: [0, 0, 0, 0],
+                expand: name == "rotate-deg-expand",
+            }),
+            "deskew" => Ok(Operation::Deskew(
+                value.map(|value| parse_positive(value).map(|angle| angle as f32)).transpose()?.unwrap_or(DESKEW_MAX_ANGLE),
+            )),
+            "fliph" => Ok(Operation::FlipH),
+            "flipv" => Ok(Operation::FlipV),
+            "invert" => Ok(Operation::Invert),
+            "grayscale" => Ok(Operation::Grayscale),
+            "sepia" => Ok(Operation::Sepia),
+            "swap-channels" => Ok(Operation::SwapChannels(parse_channel_order(require_value(name, value)?)?)),
+            "extract-channel" => {
+                let value = require_value(name, value)?;
+                let channel = Channel::from_str(value, true).map_err(|_| format!("Invalid extract-channel value: {}", value))?;
+                Ok(Operation::ExtractChannel(channel))
+            }
+            // posterize=LEVELS[:DITHER] and palette=COLORS[:DITHER]
+            "posterize" => {
+                let (levels, dither) = split_dither(value)?;
+                match parse_value(name, levels)? {
+                    levels @ 2..=256 => Ok(Operation::Posterize { levels, dither }),
+                    levels => Err(format!("Invalid posterize value: {} (must be 2-256)", levels)),
+                }
+            }
+            "palette" => {
+                let (colors, dither) = split_dither(value)?;
+                match parse_value(name, colors)? {
+                    colors @ 1..=256 => Ok(Operation::Quantize { colors, dither }),
+                    colors => Err(format!("Invalid palette value: {} (must be 1-256)", colors)),
+                }
+            }
+            "threshold" => Ok(Operation::Threshold(parse_value(name, value)?)),
+            "edges" => Ok(Operation::Edges),
+            "dog" | "dog-normalize" => {
+                let (sigma1, sigma2) = parse_dog(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::DifferenceOfGaussians { sigma1, sigma2, normalize: name == "dog-normalize" })
+            }
+            "vignette" => Ok(Operation::Vignette(parse_fraction(require_value(name, value)?)?)),
+            "bloom" => {
+                let (threshold, intensity) = parse_bloom(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::Bloom { threshold, intensity })
+            }
+            "oil" => {
+                let (radius, intensity) = parse_oil(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::Oil { radius, intensity })
+            }
+            "colorkey" => {
+                let (r, g, b, tolerance) = parse_colorkey(&require_value(name, value)?.replace(':', ","))?;
+                Ok(Operation::ColorKey { r, g, b, tolerance })
+            }
+            "circle" => Ok(Operation::Circle),
+            "round-corners" => Ok(Operation::RoundCorners(parse_value(name, value)?)),
+            "as" => {
+                let value = require_value(name, value)?;
+                let target = ColorTarget::from_str(value, true).map_err(|_| format!("Invalid as value: {}", value))?;
+                Ok(Operation::ConvertColor(target))
+            }
+            _ => Err(format!("Unknown operation: {}", name)),
+        }
+    }
+}
+
+// Splits an optional `:DITHER` suffix off an operation value.
+fn split_dither(value: Option<&str>) -> Result<(Option<&str>, Dither), String> {
+    match value.and_then(|value| value.split_once(':')) {
+        Some((count, method)) => {
+            let dither = Dither::from_str(method, true).map_err(|_| format!("Invalid dither value: {}", method))?;
+            Ok((Some(count), dither))
+        }
+        None => Ok((value, Dither::None)),
+    }
+}
+
+fn require_value<'a>(name: &str, value: Option<&'a str>) -> Result<&'a str, String> {
+    value.ok_or_else(|| format!("Operation {} requires a value", name))
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<&str>) -> Result<T, String> {
+    let value = require_value(name, value)?;
+    value.parse().map_err(|_| format!("Invalid {} value: {}", name, value))
+}
+
+pub fn parse_pipeline(s: &str) -> Result<Vec<Operation>, String> {
+    s.split(',').map(str::parse).collect()
+}
+
+pub fn apply_pipeline(img: DynamicImage, ops: &[Operation]) -> DynamicImage {
+    ops.iter().fold(img, |img, op| time(|| op.to_string(), || op.apply(img)))
+}
+
+// Applies the operations to the X,Y,W,H region only and pastes the result
+// back over the original. The region is clipped to the image, and every
+// operation must keep the dimensions of its input.
+pub fn apply_pipeline_in_region(img: DynamicImage, ops: &[Operation], region: (u32, u32, u32, u32)) -> DynamicImage {
+    debug_assert!(ops.iter().all(Operation::keeps_dimensions));
+    let (x, y, width, height) = region;
+    let (x, y) = (x.min(img.width()), y.min(img.height()));
+    let (width, height) = (width.min(img.width() - x), height.min(img.height() - y));
+    if width == 0 || height == 0 {
+        return img;
+    }
+    let part = apply_pipeline(img.crop_imm(x, y, width, height), ops);
+    if img.color().has_alpha() || part.color().has_alpha() {
+        let mut canvas = img.to_rgba8();
+        imageops::replace(&mut canvas, &part.to_rgba8(), x as i64, y as i64);
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        let mut canvas = img.to_rgb8();
+        imageops::replace(&mut canvas, &part.to_rgb8(), x as i64, y as i64);
+        DynamicImage::ImageRgb8(canvas)
+    }
+}
@@ -0,0 +1,327 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::lut::load_lut;
+use crate::parse::parse_channel_order;
+use crate::pipeline::Operation;
+use crate::transform::{
+    Channel, ColorTarget, Dither, PadMode, DESKEW_MAX_ANGLE, OIL_INTENSITY, OIL_RADIUS,
+};
+
+// A reusable list of operations read from a TOML file, e.g.
+//
+//     [[op]]
+//     kind = "blur"
+//     sigma = 2.0
+//
+//     [[op]]
+//     kind = "rotate"
+//     degrees = 90
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PresetFile {
+    #[serde(default)]
+    op: Vec<PresetOp>,
+}
+
+// The TOML form of each `Operation`, with every parameter named.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+enum PresetOp {
+    Blur { sigma: f32 },
+    FastBlur { radius: u32 },
+    Median { radius: u32 },
+    Pixelate {
+        block: u32,
+        #[serde(default)]
+        region: Option<[u32; 4]>,
+    },
+    Sharpen { sigma: f32, threshold: i32 },
+    Convolve { kernel: [f32; 9] },
+    Emboss,
+    Brighten { value: i32 },
+    Levels { r: i32, g: i32, b: i32 },
+    Contrast { value: f32 },
+    AutoContrast {
+        #[serde(default)]
+        clip: f32,
+    },
+    Gamma { value: f32 },
+    // Read from `path`, relative to the working directory.
+    Lut { path: String },
+    Huerotate { degrees: i32 },
+    Temperature { offset: i32 },
+    KeepHue { hue: f32, tolerance: f32 },
+    Saturate { factor: f32 },
+    Resize {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        keep_aspect: bool,
+    },
+    PixelResize { width: u32, height: u32 },
+    Thumbnail { width: u32, height: u32 },
+    SeamCarve { width: u32, height: u32 },
+    Scale { factor: f32 },
+    Crop { x: u32, y: u32, width: u32, height: u32 },
+    Trim {
+        color: Option<[u8; 3]>,
+        #[serde(default)]
+        fuzz: f32,
+    },
+    CropPct { x: f32, y: f32, width: f32, height: f32 },
+    CropAspect { width: u32, height: u32 },
+    Border {
+        width: u32,
+        #[serde(default)]
+        color: [u8; 3],
+    },
+    Pad {
+        width: u32,
+        mode: PadMode,
+        fill: Option<[u8; 3]>,
+    },
+    Rotate { degrees: i32 },
+    RotateDeg {
+        degrees: f32,
+        #[serde(default)]
+        fill: [u8; 4],
+        #[serde(default)]
+        expand: bool,
+    },
+    Deskew {
+        #[serde(default = "default_deskew_max_angle")]
+        max_angle: f32,
+    },
+    Fliph,
+    Flipv,
+    Invert,
+    Grayscale,
+    Sepia,
+    SwapChannels { order: String },
+    ExtractChannel { channel: Channel },
+    Posterize {
+        levels: u16,
+        #[serde(default)]
+        dither: Dither,
+    },
+    Palette {
+        colors: u16,
+        #[serde(default)]
+        dither: Dither,
+    },
+    Threshold { value: u8 },
+    Edges,
+    Dog {
+        sigma1: f32,
+        sigma2: f32,
+        #[serde(default)]
+        normalize: bool,
+    },
+    Vignette { strength: f32 },
+    Bloom { threshold: u8, intensity: f32 },
+    Oil {
+        #[serde(default = "default_oil_radius")]
+        radius: u32,
+        #[serde(default = "default_oil_intensity")]
+        intensity: u32,
+    },
+    Colorkey { r: u8, g: u8, b: u8, tolerance: f32 },
+    Circle,
+    RoundCorners { radius: u32 },
+    As { color_type: ColorTarget },
+}
+
+fn default_deskew_max_angle() -> f32 {
+    DESKEW_MAX_ANGLE
+}
+
+fn default_oil_radius() -> u32 {
+    OIL_RADIUS
+}
+
+fn default_oil_intensity() -> u32 {
+    OIL_INTENSITY
+}
+
+impl TryFrom<PresetOp> for Operation {
+    type Error = String;
+
+    fn try_from(op: PresetOp) -> Result<Self, Self::Error> {
+        Ok(match op {
+            PresetOp::Blur { sigma } => Operation::Blur(sigma),
+            PresetOp::FastBlur { radius } => Operation::FastBlur(radius),
+            PresetOp::Median { radius } => Operation::Median(radius),
+            PresetOp::Pixelate { block: 0, .. } => return Err("Pixelate block size must be greater than 0".to_string()),
+            PresetOp::Pixelate { block, region } => {
+                Operation::Pixelate { block, region: region.map(|[x, y, width, height]| (x, y, width, height)) }
+            }
+            PresetOp::Sharpen { sigma, threshold } => Operation::Sharpen { sigma, threshold },
+            PresetOp::Convolve { kernel } => Operation::Convolve(kernel),
+            PresetOp::Emboss => Operation::Emboss,
+            PresetOp::Brighten { value } => Operation::Brighten(value),
+            PresetOp::Levels { r, g, b } => Operation::Levels { r, g, b },
+            PresetOp::Contrast { value } => Operation::Contrast(value),
+            PresetOp::AutoContrast { clip } => {
+                if !(0.0..=100.0).contains(&clip) {
+                    return Err(format!("Auto contrast clip must be between 0 and 100: {}", clip));
+                }
+                Operation::AutoContrast(clip)
+            }
+            PresetOp::Gamma { value } => {
+                if !(value > 0.0 && value.is_finite()) {
+                    return Err(format!("Gamma must be greater than 0: {}", value));
+                }
+                Operation::Gamma(value)
+            }
+            PresetOp::Lut { path } => Operation::Lut(Box::new(load_lut(&path)?)),
+            PresetOp::Huerotate { degrees } => Operation::HueRotate(degrees),
+            PresetOp::Temperature { offset: offset @ -100..=100 } => Operation::Temperature(offset),
+            PresetOp::Temperature { offset } => {
+                return Err(format!("Temperature must be between -100 and 100: {}", offset))
+            }
+            PresetOp::KeepHue { hue, tolerance } => {
+                if !(tolerance >= 0.0 && hue.is_finite() && tolerance.is_finite()) {
+                    return Err(format!("Invalid keep-hue value: {}, {}", hue, tolerance));
+                }
+                Operation::KeepHue { hue, tolerance }
+            }
+            PresetOp::Saturate { factor } => Operation::Saturate(factor),
+            PresetOp::Resize { width, height, keep_aspect } => {
+                if width == 0 || height == 0 {
+                    return Err(format!("Width and height must be greater than zero: {}x{}", width, height));
+                }
+                Operation::Resize { width, height, keep_aspect }
+            }
+            PresetOp::PixelResize { width, height } => {
+                if width == 0 || height == 0 {
+                    return Err(format!("Width and height must be greater than zero: {}x{}", width, height));
+                }
+                Operation::PixelResize { width, height }
+            }
+            PresetOp::Thumbnail { width, height } => {
+                if width == 0 || height == 0 {
+                    return Err(format!("Width and height must be greater than zero: {}x{}", width, height));
+                }
+                Operation::Thumbnail { width, height }
+            }
+            PresetOp::SeamCarve { width, height } => {
+                if width == 0 || height == 0 {
+                    return Err(format!("Width and height must be greater than zero: {}x{}", width, height));
+                }
+                Operation::SeamCarve { width, height }
+            }
+            PresetOp::Scale { factor } => {
+                if !(factor > 0.0 && factor.is_finite()) {
+                    return Err(format!("Scale factor must be greater than 0: {}", factor));
+                }
+                Operation::Scale(factor)
+            }
+            PresetOp::Crop { x, y, width, height } => Operation::Crop { x, y, width, height },
+            PresetOp::Trim { color, fuzz } => {
+                if !(fuzz >= 0.0 && fuzz.is_finite()) {
+                    return Err(format!("Trim fuzz must not be negative: {}", fuzz));
+                }
+                Operation::Trim { color, fuzz }
+            }
+            PresetOp::CropPct { x, y, width, height } => {
+                let in_range = [x, y, width, height].iter().all(|value| (0.0..=100.0).contains(value));
+                if !in_range || x + width > 100.0 || y + height > 100.0 {
+                    return Err(format!("Crop region extends past the image: {}%,{}%,{}%,{}%", x, y, width, height));
+                }
+                if width == 0.0 || height == 0.0 {
+                    return Err(format!("Crop width and height must be greater than 0%: {}%,{}%", width, height));
+                }
+                Operation::CropPct { x, y, width, height }
+            }
+            PresetOp::CropAspect { width, height } => {
+                if width == 0 || height == 0 {
+                    return Err(format!("Aspect ratio components must be greater than zero: {}:{}", width, height));
+                }
+                Operation::CropAspect { width, height }
+            }
+            PresetOp::Border { width, color } => Operation::Border { width, color },
+            PresetOp::Pad { width, mode, fill } => {
+                if fill.is_some() && mode != PadMode::Constant {
+                    return Err(format!("Only constant padding takes a fill color, not {}", mode));
+                }
+                Operation::Pad { width, mode, fill: fill.unwrap_or_default() }
+            }
+            PresetOp::Rotate { degrees } => Operation::Rotate(degrees),
+            PresetOp::RotateDeg { degrees, fill, expand } => Operation::RotateDeg { degrees, fill, expand },
+            PresetOp::Deskew { max_angle } => {
+                if !(max_angle > 0.0 && max_angle.is_finite()) {
+                    return Err(format!("Deskew max angle must be greater than 0: {}", max_angle));
+                }
+                Operation::Deskew(max_angle)
+            }
+            PresetOp::Fliph => Operation::FlipH,
+            PresetOp::Flipv => Operation::FlipV,
+            PresetOp::Invert => Operation::Invert,
+            PresetOp::Grayscale => Operation::Grayscale,
+            PresetOp::Sepia => Operation::Sepia,
+            PresetOp::SwapChannels { order } => Operation::SwapChannels(parse_channel_order(&order)?),
+            PresetOp::ExtractChannel { channel } => Operation::ExtractChannel(channel),
+            PresetOp::Posterize { levels: levels @ 2..=256, dither } => Operation::Posterize { levels, dither },
+            PresetOp::Posterize { levels, .. } => {
+                return Err(format!("Invalid posterize value: {} (must be 2-256)", levels))
+            }
+            PresetOp::Palette { colors: colors @ 1..=256, dither } => Operation::Quantize { colors, dither },
+            PresetOp::Palette { colors, .. } => {
+                return Err(format!("Invalid palette value: {} (must be 1-256)", colors))
+            }
+            PresetOp::Threshold { value } => Operation::Threshold(value),
+            PresetOp::Edges => Operation::Edges,
+            PresetOp::Dog { sigma1, sigma2, normalize } => {
+                if !(sigma1 > 0.0 && sigma2 > 0.0 && sigma1.is_finite() && sigma2.is_finite()) {
+                    return Err(format!("Dog sigmas must be greater than 0: {}, {}", sigma1, sigma2));
+                }
+                Operation::DifferenceOfGaussians { sigma1, sigma2, normalize }
+            }
+            PresetOp::Vignette { strength } => {
+                if !(0.0..=1.0).contains(&strength) {
+                    return Err(format!("Vignette strength must be between 0.0 and 1.0: {}", strength));
+                }
+                Operation::Vignette(strength)
+            }
+            PresetOp::Bloom { threshold, intensity } => {
+                if !(intensity >= 0.0 && intensity.is_finite()) {
+                    return Err(format!("Bloom intensity must not be negative: {}", intensity));
+                }
+                Operation::Bloom { threshold, intensity }
+            }
+            PresetOp::Oil { radius, intensity } => {
+                if !(1..=256).contains(&intensity) {
+                    return Err(format!("Invalid oil intensity: {} (must be 1-256)", intensity));
+                }
+                Operation::Oil { radius, intensity }
+            }
+            PresetOp::Colorkey { r, g, b, tolerance } => {
+                if !(tolerance >= 0.0 && tolerance.is_finite()) {
+                    return Err(format!("Colorkey tolerance must not be negative: {}", tolerance));
+                }
+                Operation::ColorKey { r, g, b, tolerance }
+            }
+            PresetOp::Circle => Operation::Circle,
+            PresetOp::RoundCorners { radius } => Operation::RoundCorners(radius),
+            PresetOp::As { color_type } => Operation::ConvertColor(color_type),
+        })
+    }
+}
+
+pub fn parse_preset(s: &str) -> Result<Preset, String> {
+    let file: PresetFile = toml::from_str(s).map_err(|err| format!("Invalid preset: {}", err))?;
+    let operations = file.op.into_iter().map(Operation::try_from).collect::<Result<_, _>>()?;
+    Ok(Preset { operations })
+}
+
+pub fn load_preset(path: &str) -> Result<Preset, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("Failed to read preset {}: {}", path, err))?;
+    parse_preset(&contents)
+}
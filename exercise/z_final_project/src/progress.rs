@@ -0,0 +1,81 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::log::{self, LogFormat};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+// Turns progress reporting on or off for every long running operation.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// Asks long running loops to stop early, e.g. on Ctrl-C. Returns whether
+// cancelling had already been asked for.
+pub fn cancel() -> bool {
+    CANCELLED.swap(true, Ordering::Relaxed)
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+// Reports completed steps as a percentage on stderr. On a terminal the line is
+// redrawn in place, otherwise a new line is printed every 10 percent so logs
+// are not flooded. Safe to share between threads.
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    done: AtomicUsize,
+    enabled: bool,
+    tty: bool,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize) -> Self {
+        Progress {
+            label,
+            total: total.max(1),
+            done: AtomicUsize::new(0),
+            enabled: is_enabled(),
+            tty: io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn inc(&self) {
+        if !self.enabled {
+            return;
+        }
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let step = if self.redraw() { 1 } else { 10 };
+        let percent = done * 100 / self.total;
+        let previous = (done - 1) * 100 / self.total;
+        if percent / step == previous / step {
+            return;
+        }
+        if log::format() == LogFormat::Json {
+            log::info(&format!("{}: {}%", self.label, percent));
+        } else if self.tty {
+            eprint!("\r{}: {:3}%", self.label, percent);
+            let _ = io::stderr().flush();
+        } else {
+            eprintln!("{}: {}%", self.label, percent);
+        }
+    }
+
+    pub fn finish(&self) {
+        if self.enabled && self.redraw() {
+            eprintln!();
+        }
+    }
+
+    // JSON logs get one record per step even on a terminal.
+    fn redraw(&self) -> bool {
+        self.tty && log::format() == LogFormat::Text
+    }
+}
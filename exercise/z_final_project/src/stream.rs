@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use image::{DynamicImage, ImageBuffer};
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+use crate::pipeline::{apply_pipeline, Operation};
+use crate::progress::Progress;
+
+// Number of rows decoded, transformed and encoded at a time.
+const CHUNK_ROWS: u32 = 64;
+
+// Applies `ops` to a PNG a chunk of rows at a time, so only a small slice of
+// the image is ever held in memory. Only per-pixel operations can be applied
+// this way (see `Operation::is_streamable`), and interlaced PNGs are rejected
+// since their rows are not stored in order.
+pub fn stream_png(infile: &Path, outfile: &Path, ops: &[Operation]) -> io::Result<()> {
+    if let Some(op) = ops.iter().find(|op| !op.is_streamable()) {
+        return Err(invalid_input(format!(
+            "{} can't be streamed, only per-pixel operations such as grayscale, invert, \
+             brighten, levels, sepia and threshold can",
+            op
+        )));
+    }
+
+    let mut decoder = Decoder::new(BufReader::new(File::open(infile)?));
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    if reader.info().interlaced {
+        return Err(invalid_input(format!("{} is interlaced and can't be streamed", infile.display())));
+    }
+    let (width, height) = (reader.info().width, reader.info().height);
+    let (color, _) = reader.output_color_type();
+    let line_size = reader.output_line_size(width);
+
+    let progress = Progress::new("Streaming", height as usize);
+    let mut writer = None;
+    let mut y = 0;
+    while y < height {
+        let rows = CHUNK_ROWS.min(height - y);
+        let mut data = Vec::with_capacity(line_size * rows as usize);
+        for _ in 0..rows {
+            let row = reader.next_row()?.ok_or(io::ErrorKind::UnexpectedEof)?;
+            data.extend_from_slice(row.data());
+            progress.inc();
+        }
+
+        let (color, data) = to_png_data(apply_pipeline(to_image(color, width, rows, data), ops));
+        let stream = match &mut writer {
+            Some(stream) => stream,
+            None => {
+                let mut encoder = Encoder::new(BufWriter::new(File::create(outfile)?), width, height);
+                encoder.set_color(color);
+                encoder.set_depth(BitDepth::Eight);
+                writer.insert(encoder.write_header()?.into_stream_writer()?)
+            }
+        };
+        stream.write_all(&data)?;
+        y += rows;
+    }
+    progress.finish();
+
+    if let Some(stream) = writer {
+        stream.finish()?;
+    }
+    Ok(())
+}
+
+fn to_image(color: ColorType, width: u32, height: u32, data: Vec<u8>) -> DynamicImage {
+    let img = match color {
+        ColorType::Grayscale => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+        ColorType::GrayscaleAlpha => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8),
+        ColorType::Rgba => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+        // Indexed images are expanded to RGB by the decoder.
+        ColorType::Rgb | ColorType::Indexed => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+    };
+    img.expect("decoded rows match the image dimensions")
+}
+
+fn to_png_data(img: DynamicImage) -> (ColorType, Vec<u8>) {
+    match img {
+        DynamicImage::ImageLuma8(buf) => (ColorType::Grayscale, buf.into_raw()),
+        DynamicImage::ImageLumaA8(buf) => (ColorType::GrayscaleAlpha, buf.into_raw()),
+        DynamicImage::ImageRgb8(buf) => (ColorType::Rgb, buf.into_raw()),
+        DynamicImage::ImageRgba8(buf) => (ColorType::Rgba, buf.into_raw()),
+        img if img.color().has_alpha() => (ColorType::Rgba, img.to_rgba8().into_raw()),
+        img => (ColorType::Rgb, img.to_rgb8().into_raw()),
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
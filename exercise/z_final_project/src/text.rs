@@ -0,0 +1,43 @@
+use image::{DynamicImage, Rgba};
+use imageproc::drawing::draw_text_mut;
+use rusttype::{Font, Scale};
+
+static DEFAULT_FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+// DejaVu Sans, bundled so captions work without any fonts installed.
+pub fn default_font() -> Font<'static> {
+    Font::try_from_bytes(DEFAULT_FONT).expect("bundled font is valid")
+}
+
+pub fn load_font(bytes: Vec<u8>) -> Option<Font<'static>> {
+    Font::try_from_vec(bytes)
+}
+
+// Draws `text` with its top-left corner at (x, y), `size` pixels tall. Each
+// '\n' starts a new line one line height further down.
+pub fn draw_text(
+    img: DynamicImage,
+    text: &str,
+    x: i32,
+    y: i32,
+    size: f32,
+    color: [u8; 3],
+    font: &Font,
+) -> DynamicImage {
+    let keep_alpha = img.color().has_alpha();
+    let mut canvas = img.to_rgba8();
+    let scale = Scale::uniform(size);
+    let metrics = font.v_metrics(scale);
+    let line_height = (metrics.ascent - metrics.descent + metrics.line_gap).ceil() as i32;
+    let [r, g, b] = color;
+
+    for (i, line) in (0..).zip(text.lines()) {
+        draw_text_mut(&mut canvas, Rgba([r, g, b, 255]), x, y + i * line_height, scale, font, line);
+    }
+
+    if keep_alpha {
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+    }
+}
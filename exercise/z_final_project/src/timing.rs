@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::log;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Turns reporting how long each step takes on or off.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// A `label: duration` line, the unit picked to suit the duration.
+pub fn format_timing(label: &str, elapsed: Duration) -> String {
+    format!("{}: {:.1?}", label, elapsed)
+}
+
+// Runs `step` and logs its wall-clock time under `label` when enabled. The
+// label is only built when it's needed.
+pub fn time<T, L: FnOnce() -> String>(label: L, step: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return step();
+    }
+    let start = Instant::now();
+    let result = step();
+    log::info(&format_timing(&label(), start.elapsed()));
+    result
+}
@@ -0,0 +1,1326 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use clap::ValueEnum;
+use image::imageops::{self, FilterType};
+use image::{
+    ColorType, DynamicImage, GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage, Rgba, RgbaImage,
+};
+use serde::Deserialize;
+
+use crate::color::{hsv_to_rgb, rgb_to_hsv};
+use crate::log;
+use crate::progress::Progress;
+
+pub fn blur(img: DynamicImage, sigma: f32) -> DynamicImage {
+    img.blur(sigma)
+}
+
+// Three passes of a separable box blur, which approximates a Gaussian with a
+// sigma of about sqrt(radius * (radius + 1)). Each pass costs the same no
+// matter how large the radius is.
+pub fn fast_blur(img: DynamicImage, radius: u32) -> DynamicImage {
+    if radius == 0 {
+        return img;
+    }
+    if img.color().has_alpha() {
+        let mut buf = img.to_rgba8();
+        let (width, height) = buf.dimensions();
+        box_blur(&mut buf, width as usize, height as usize, 4, radius as usize);
+        DynamicImage::ImageRgba8(buf)
+    } else {
+        let mut buf = img.to_rgb8();
+        let (width, height) = buf.dimensions();
+        box_blur(&mut buf, width as usize, height as usize, 3, radius as usize);
+        DynamicImage::ImageRgb8(buf)
+    }
+}
+
+// Replaces each channel with its median over the (2 * radius + 1) square
+// around the pixel, which removes speckles without softening edges. Pixels
+// past the border are treated as copies of the nearest edge pixel.
+pub fn median(img: DynamicImage, radius: u32) -> DynamicImage {
+    if radius == 0 {
+        return img;
+    }
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(median_filter(&img.to_rgba8(), radius))
+    } else {
+        DynamicImage::ImageRgb8(median_filter(&img.to_rgb8(), radius))
+    }
+}
+
+// Defaults for presets that leave out the oil painting settings.
+pub const OIL_RADIUS: u32 = 3;
+pub const OIL_INTENSITY: u32 = 20;
+
+// Oil painting effect. The pixels within `radius` are sorted into `intensity`
+// brightness levels and each pixel takes the average color of the most
+// crowded level, so areas of similar brightness flatten into brush strokes.
+// Every pixel looks at its whole (2 * radius + 1) square, so the cost grows
+// with the square of the radius.
+pub fn oil_paint(img: DynamicImage, radius: u32, intensity: u32) -> DynamicImage {
+    if radius == 0 {
+        return img;
+    }
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(oil_filter(&img.to_rgba8(), radius, intensity))
+    } else {
+        DynamicImage::ImageRgb8(oil_filter(&img.to_rgb8(), radius, intensity))
+    }
+}
+
+// Replaces each BLOCK x BLOCK cell with its average color. With a region only
+// the cells inside X,Y,W,H are touched, starting from its top left corner;
+// cells cut off by the right or bottom edge average the pixels they cover.
+pub fn pixelate(img: DynamicImage, block: u32, region: Option<(u32, u32, u32, u32)>) -> DynamicImage {
+    if block <= 1 {
+        return img;
+    }
+    let (width, height) = (img.width(), img.height());
+    let (x, y, w, h) = region.unwrap_or((0, 0, width, height));
+    let bounds = (x.min(width), y.min(height), x.saturating_add(w).min(width), y.saturating_add(h).min(height));
+    if img.color().has_alpha() {
+        let mut buf = img.to_rgba8();
+        average_blocks(&mut buf, block, bounds);
+        DynamicImage::ImageRgba8(buf)
+    } else {
+        let mut buf = img.to_rgb8();
+        average_blocks(&mut buf, block, bounds);
+        DynamicImage::ImageRgb8(buf)
+    }
+}
+
+pub fn sharpen(img: DynamicImage, sigma: f32, threshold: i32) -> DynamicImage {
+    img.unsharpen(sigma, threshold)
+}
+
+// Convolves each channel independently with a row-major 3x3 kernel. The
+// kernel is normalized by its sum unless the sum is zero.
+pub fn convolve(img: DynamicImage, kernel: [f32; 9]) -> DynamicImage {
+    img.filter3x3(&kernel)
+}
+
+// The kernel sums to 1, so flat areas keep their original tone and only edges
+// are raised or sunk.
+pub fn emboss(img: DynamicImage) -> DynamicImage {
+    convolve(img, [-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0])
+}
+
+// `value` is on the 0-255 scale whatever the depth of the image, so 16-bit
+// images are brightened by the same amount without being converted to 8-bit.
+pub fn brighten(img: DynamicImage, value: i32) -> DynamicImage {
+    match img.color() {
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => img.brighten(value.saturating_mul(257)),
+        _ => img.brighten(value),
+    }
+}
+
+pub fn levels(img: DynamicImage, dr: i32, dg: i32, db: i32) -> DynamicImage {
+    let shift = |value: u8, delta: i32| (value as i32).saturating_add(delta).clamp(0, 255) as u8;
+    map_rgb(img, |Rgb([r, g, b])| Rgb([shift(r, dr), shift(g, dg), shift(b, db)]))
+}
+
+// Applies `out = 255 * (in / 255)^(1 / g)` to the color channels through a
+// lookup table, so each channel value is only computed once.
+pub fn gamma(img: DynamicImage, g: f32) -> DynamicImage {
+    if g == 1.0 {
+        return img;
+    }
+    let table: [u8; 256] = std::array::from_fn(|i| (255.0 * (i as f32 / 255.0).powf(1.0 / g)).round() as u8);
+    map_rgb(img, |Rgb([r, g, b])| Rgb([table[r as usize], table[g as usize], table[b as usize]]))
+}
+
+pub fn apply_lut(img: DynamicImage, table: &[[u8; 256]; 3]) -> DynamicImage {
+    let [red, green, blue] = table;
+    map_rgb(img, |Rgb([r, g, b])| Rgb([red[r as usize], green[g as usize], blue[b as usize]]))
+}
+
+pub fn contrast(img: DynamicImage, value: f32) -> DynamicImage {
+    img.adjust_contrast(value)
+}
+
+// Stretches the luminance range of the image to 0-255, ignoring the darkest
+// and brightest `clip` percent of pixels. Each channel is rescaled with the
+// same mapping so colors don't shift. Flat images are returned unchanged.
+pub fn auto_contrast(img: DynamicImage, clip: f32) -> DynamicImage {
+    let luma = img.to_luma8();
+    let mut hist = [0u64; 256];
+    for pixel in luma.pixels() {
+        hist[pixel[0] as usize] += 1;
+    }
+    let clipped = (luma.len() as f64 * clip as f64 / 100.0) as u64;
+    let mut seen = 0;
+    let low = (0..256).find(|&value| {
+        seen += hist[value];
+        seen > clipped
+    });
+    seen = 0;
+    let high = (0..256).rev().find(|&value| {
+        seen += hist[value];
+        seen > clipped
+    });
+    let (Some(low), Some(high)) = (low, high) else {
+        return img;
+    };
+    if high <= low {
+        return img;
+    }
+    let table: [u8; 256] = std::array::from_fn(|i| {
+        let stretched = (i as f32 - low as f32) * 255.0 / (high - low) as f32;
+        stretched.round().clamp(0.0, 255.0) as u8
+    });
+    map_rgb(img, |Rgb([r, g, b])| Rgb([table[r as usize], table[g as usize], table[b as usize]]))
+}
+
+pub fn huerotate(img: DynamicImage, degrees: i32) -> DynamicImage {
+    img.huerotate(degrees)
+}
+
+pub fn saturate(img: DynamicImage, factor: f32) -> DynamicImage {
+    // HSV with zero saturation collapses to the brightest channel rather than
+    // the luma, so full desaturation goes through grayscale to match it.
+    if factor == 0.0 {
+        return img.grayscale();
+    }
+    map_rgb(img, |pixel| {
+        let (hue, saturation, value) = rgb_to_hsv(pixel);
+        hsv_to_rgb(hue, (saturation * factor as f64).clamp(0.0, 1.0), value)
+    })
+}
+
+// Warms the image for a positive `offset` by scaling red up and blue down, or
+// cools it for a negative one. At +-100 the channels change by 20%.
+pub fn temperature(img: DynamicImage, offset: i32) -> DynamicImage {
+    if offset == 0 {
+        return img;
+    }
+    let shift = offset.clamp(-100, 100) as f32 / 500.0;
+    let scale = |value: u8, factor: f32| (value as f32 * factor).round().clamp(0.0, 255.0) as u8;
+    map_rgb(img, |Rgb([r, g, b])| Rgb([scale(r, 1.0 + shift), g, scale(b, 1.0 - shift)]))
+}
+
+pub fn resize(img: DynamicImage, width: u32, height: u32, keep_aspect: bool) -> DynamicImage {
+    if keep_aspect {
+        img.resize(width, height, FilterType::Lanczos3)
+    } else {
+        img.resize_exact(width, height, FilterType::Lanczos3)
+    }
+}
+
+// Resizes with nearest-neighbor sampling so every pixel stays a hard edged
+// block, for pixel art.
+pub fn pixel_resize(img: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    img.resize_exact(width, height, FilterType::Nearest)
+}
+
+// Shrinks the image to fit within WIDTHxHEIGHT keeping its aspect ratio.
+// Images that already fit are returned as they are rather than enlarged.
+pub fn thumbnail(img: DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+    if img.width() <= max_width && img.height() <= max_height {
+        return img;
+    }
+    img.thumbnail(max_width, max_height)
+}
+
+// Resizes both dimensions by `factor`, never going below 1x1.
+pub fn scale(img: DynamicImage, factor: f32) -> DynamicImage {
+    let width = (img.width() as f32 * factor).round().max(1.0) as u32;
+    let height = (img.height() as f32 * factor).round().max(1.0) as u32;
+    img.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+// Content aware resize to WIDTHxHEIGHT by repeatedly removing the connected
+// vertical or horizontal seam of pixels with the least Sobel energy. Every seam
+// recomputes the energy of the whole image, so n seams cost O(n * w * h).
+// Seams only shrink the image and at most half of each dimension is carved;
+// any further reduction is finished with a plain resize.
+pub fn seam_carve(img: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if img.width() == 0 || img.height() == 0 {
+        return img;
+    }
+    let (width, height) = (width.clamp(1, img.width()), height.clamp(1, img.height()));
+    let carved_width = width.max(img.width() / 2);
+    let carved_height = height.max(img.height() / 2);
+    let progress = Progress::new("Carving", (img.width() - carved_width + img.height() - carved_height) as usize);
+    let keep_alpha = img.color().has_alpha();
+    let buf = remove_seams(img.to_rgba8(), img.width() - carved_width, &progress);
+    // Horizontal seams are vertical seams of the image turned on its side.
+    let buf = imageops::rotate90(&remove_seams(imageops::rotate270(&buf), img.height() - carved_height, &progress));
+    progress.finish();
+    let carved = if keep_alpha {
+        DynamicImage::ImageRgba8(buf)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(buf).to_rgb8())
+    };
+    if (carved_width, carved_height) == (width, height) {
+        carved
+    } else {
+        carved.resize_exact(width, height, FilterType::Lanczos3)
+    }
+}
+
+pub fn crop(mut img: DynamicImage, x: u32, y: u32, width: u32, height: u32) -> DynamicImage {
+    img.crop(x, y, width, height)
+}
+
+// Crops away the border of pixels within `fuzz` (a distance in RGBA space)
+// of `color`, or of the top-left pixel when no color is given. An image that
+// is all border is returned unchanged with a warning.
+pub fn trim(img: DynamicImage, color: Option<[u8; 3]>, fuzz: f32) -> DynamicImage {
+    match trim_bounds(&img, color, fuzz) {
+        Some((x, y, width, height)) => img.crop_imm(x, y, width, height),
+        None => {
+            log::warn("--trim found nothing but background, the image is left as is");
+            img
+        }
+    }
+}
+
+// The X,Y,W,H box around the pixels `trim` keeps, None if there are none.
+pub fn trim_bounds(img: &DynamicImage, color: Option<[u8; 3]>, fuzz: f32) -> Option<(u32, u32, u32, u32)> {
+    let buf = img.to_rgba8();
+    let background = match color {
+        Some([r, g, b]) => Rgba([r, g, b, 255]),
+        None => *buf.get_pixel_checked(0, 0)?,
+    };
+    let is_background = |pixel: &Rgba<u8>| {
+        let distance: f32 = (0..4).map(|i| (pixel[i] as f32 - background[i] as f32).powi(2)).sum::<f32>().sqrt();
+        distance <= fuzz
+    };
+
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in buf.enumerate_pixels() {
+        if !is_background(pixel) {
+            (left, top) = (left.min(x), top.min(y));
+            (right, bottom) = (right.max(x), bottom.max(y));
+        }
+    }
+    (left <= right).then(|| (left, top, right - left + 1, bottom - top + 1))
+}
+
+// Crops a region given as percentages (0-100) of the image dimensions. The
+// region is always at least one pixel wide and tall.
+pub fn crop_pct(img: DynamicImage, x: f32, y: f32, width: f32, height: f32) -> DynamicImage {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let left = (x / 100.0 * w).round() as u32;
+    let top = (y / 100.0 * h).round() as u32;
+    let right = ((x + width) / 100.0 * w).round().min(w) as u32;
+    let bottom = ((y + height) / 100.0 * h).round().min(h) as u32;
+    crop(img, left, top, right.saturating_sub(left).max(1), bottom.saturating_sub(top).max(1))
+}
+
+// Crops to the largest centered rectangle with the aspect ratio
+// RATIO_WIDTH:RATIO_HEIGHT.
+pub fn crop_aspect(img: DynamicImage, ratio_width: u32, ratio_height: u32) -> DynamicImage {
+    let (width, height) = (img.width() as u64, img.height() as u64);
+    let (ratio_width, ratio_height) = (ratio_width.max(1) as u64, ratio_height.max(1) as u64);
+    let (new_width, new_height) = if width * ratio_height > height * ratio_width {
+        (height * ratio_width / ratio_height, height)
+    } else {
+        (width, width * ratio_height / ratio_width)
+    };
+    let (new_width, new_height) = (new_width.max(1) as u32, new_height.max(1) as u32);
+    let x = (img.width() - new_width.min(img.width())) / 2;
+    let y = (img.height() - new_height.min(img.height())) / 2;
+    img.crop_imm(x, y, new_width, new_height)
+}
+
+// Surrounds the image with a WIDTH pixel frame of COLOR.
+pub fn border(img: DynamicImage, width: u32, color: [u8; 3]) -> DynamicImage {
+    if width == 0 {
+        return img;
+    }
+    let [r, g, b] = color;
+    let (canvas_width, canvas_height) = (img.width() + 2 * width, img.height() + 2 * width);
+    if img.color().has_alpha() {
+        let mut canvas = RgbaImage::from_pixel(canvas_width, canvas_height, Rgba([r, g, b, 255]));
+        imageops::replace(&mut canvas, &img.to_rgba8(), width as i64, width as i64);
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        let mut canvas = RgbImage::from_pixel(canvas_width, canvas_height, Rgb(color));
+        imageops::replace(&mut canvas, &img.to_rgb8(), width as i64, width as i64);
+        DynamicImage::ImageRgb8(canvas)
+    }
+}
+
+// How `pad` fills the new pixels around the image.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PadMode {
+    // Reflect the pixels next to each edge, without repeating the edge itself
+    Mirror,
+    // Repeat the outermost row or column
+    Edge,
+    // Fill with a single color
+    Constant,
+}
+
+impl fmt::Display for PadMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no variant is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+// Grows the canvas by `n` pixels on each side. `fill` is only used by
+// `PadMode::Constant`, and is opaque on images with alpha. Padding before a
+// blur or convolution keeps the dark fringe of the zero-filled edges out of
+// the result.
+pub fn pad(img: DynamicImage, n: u32, mode: PadMode, fill: [u8; 3]) -> DynamicImage {
+    if n == 0 {
+        return img;
+    }
+    let [r, g, b] = fill;
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(pad_buffer(&img.to_rgba8(), n, mode, Rgba([r, g, b, 255])))
+    } else {
+        DynamicImage::ImageRgb8(pad_buffer(&img.to_rgb8(), n, mode, Rgb(fill)))
+    }
+}
+
+pub fn rotate(img: DynamicImage, value: i32) -> DynamicImage {
+    match value {
+        90 => img.rotate90(),
+        180 => img.rotate180(),
+        270 => img.rotate270(),
+        _ => img,
+    }
+}
+
+// Rotates clockwise by any angle around the image center, sampling
+// bilinearly. Exposed corners are filled with `fill`. With `expand` the canvas
+// grows to fit the whole rotated image, otherwise it keeps its size and the
+// corners are clipped. The result is RGBA unless both the image and `fill`
+// are opaque.
+pub fn rotate_arbitrary(img: DynamicImage, degrees: f32, fill: [u8; 4], expand: bool) -> DynamicImage {
+    let keep_alpha = img.color().has_alpha() || fill[3] < 255;
+    let src = img.to_rgba8();
+    let (width, height) = (src.width() as f32, src.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (out_width, out_height) = if expand {
+        // Shave off float error so that e.g. 90 degrees doesn't add a pixel.
+        let fit = |a: f32, b: f32| ((a + b) - 1e-3).ceil().max(1.0) as u32;
+        (fit(width * cos.abs(), height * sin.abs()), fit(width * sin.abs(), height * cos.abs()))
+    } else {
+        src.dimensions()
+    };
+
+    let at = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= src.width() as i64 || y >= src.height() as i64 {
+            fill.map(|channel| channel as f32)
+        } else {
+            src.get_pixel(x as u32, y as u32).0.map(|channel| channel as f32)
+        }
+    };
+    let buf = RgbaImage::from_fn(out_width, out_height, |x, y| {
+        let dx = x as f32 + 0.5 - out_width as f32 / 2.0;
+        let dy = y as f32 + 0.5 - out_height as f32 / 2.0;
+        let sx = cos * dx + sin * dy + width / 2.0 - 0.5;
+        let sy = -sin * dx + cos * dy + height / 2.0 - 0.5;
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let (tl, tr, bl, br) = (at(x0, y0), at(x0 + 1, y0), at(x0, y0 + 1), at(x0 + 1, y0 + 1));
+        Rgba(std::array::from_fn(|i| {
+            let top = tl[i] + (tr[i] - tl[i]) * fx;
+            let bottom = bl[i] + (br[i] - bl[i]) * fx;
+            (top + (bottom - top) * fy).round() as u8
+        }))
+    });
+
+    if keep_alpha {
+        DynamicImage::ImageRgba8(buf)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(buf).to_rgb8())
+    }
+}
+
+// The default bound of the skew search, in degrees.
+pub const DESKEW_MAX_ANGLE: f32 = 10.0;
+
+// Estimates how far the dominant lines of `img` are turned clockwise from
+// horizontal or vertical, searching up to `max_angle` degrees either way in
+// steps of a tenth of a degree. Edge pixels vote for the lines through them
+// as in a Hough transform, and the angle whose votes are the most bunched up
+// wins. Returns None when no line gets votes from at least a quarter of the
+// width or height of the image.
+pub fn skew_angle(img: &DynamicImage, max_angle: f32) -> Option<f32> {
+    // Votes are counted on a smaller copy to keep large scans fast.
+    let edges = edges(thumbnail(img.clone(), 800, 800)).to_luma8();
+    let (width, height) = edges.dimensions();
+    let points: Vec<(f32, f32)> = edges
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[0] >= 128)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    let diagonal = (width as f32).hypot(height as f32).ceil() as i64;
+    let mut votes = vec![0u32; 2 * diagonal as usize + 1];
+
+    // Try the smallest angles first so that ties keep the image straighter.
+    let steps = (max_angle.clamp(0.0, 45.0) * 10.0).round() as i32;
+    let mut best: Option<(u64, f32)> = None;
+    for step in (0..=steps).flat_map(|step| [step, -step]).skip(1) {
+        let skew = step as f32 / 10.0;
+        // The normal of a line turned from horizontal, then from vertical.
+        for (normal, length) in [(90.0 + skew, width), (skew, height)] {
+            let (sin, cos) = normal.to_radians().sin_cos();
+            votes.fill(0);
+            for &(x, y) in &points {
+                votes[((x * cos + y * sin).round() as i64 + diagonal) as usize] += 1;
+            }
+            let peak = votes.iter().copied().max().unwrap_or(0);
+            let score = votes.iter().map(|&count| count as u64 * count as u64).sum();
+            if peak * 4 >= length && best.is_none_or(|(most, _)| score > most) {
+                best = Some((score, skew));
+            }
+        }
+    }
+    best.map(|(_, skew)| skew)
+}
+
+// Rotates `img` back by the angle found by `skew_angle`, keeping its size and
+// filling the exposed corners with white as on a scanned page. Images without
+// a clear skew are returned unchanged.
+pub fn deskew(img: DynamicImage, max_angle: f32) -> DynamicImage {
+    match skew_angle(&img, max_angle) {
+        Some(skew) if skew != 0.0 => rotate_arbitrary(img, -skew, [255; 4], false),
+        _ => img,
+    }
+}
+
+pub fn fliph(img: DynamicImage) -> DynamicImage {
+    img.fliph()
+}
+
+pub fn flipv(img: DynamicImage) -> DynamicImage {
+    img.flipv()
+}
+
+// Undoes an EXIF orientation (1-8) so the image displays upright.
+pub fn orient(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+pub fn invert(mut img: DynamicImage) -> DynamicImage {
+    img.invert();
+    img
+}
+
+pub fn grayscale(img: DynamicImage) -> DynamicImage {
+    img.grayscale()
+}
+
+// Sobel edge magnitude of the luma channel. Pixels past the border are
+// treated as copies of the nearest edge pixel.
+pub fn edges(img: DynamicImage) -> DynamicImage {
+    let luma = img.to_luma8();
+    let (width, height) = luma.dimensions();
+    let at = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        luma.get_pixel(x, y)[0] as f32
+    };
+
+    let buf = GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+            - at(x - 1, y - 1)
+            - 2.0 * at(x - 1, y)
+            - at(x - 1, y + 1);
+        let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+            - at(x - 1, y - 1)
+            - 2.0 * at(x, y - 1)
+            - at(x + 1, y - 1);
+        Luma([(gx * gx + gy * gy).sqrt().min(255.0) as u8])
+    });
+    DynamicImage::ImageLuma8(buf)
+}
+
+// Grayscale line art from the luma blurred at SIGMA1 minus the luma blurred
+// at SIGMA2, with negative differences clamped to 0. The differences are
+// usually faint, so `normalize` stretches the strongest one to white.
+pub fn difference_of_gaussians(img: DynamicImage, sigma1: f32, sigma2: f32, normalize: bool) -> DynamicImage {
+    let luma = DynamicImage::ImageLuma8(img.to_luma8());
+    let narrow = blur(luma.clone(), sigma1).to_luma8();
+    let wide = blur(luma, sigma2).to_luma8();
+    let mut buf = GrayImage::from_fn(narrow.width(), narrow.height(), |x, y| {
+        Luma([narrow.get_pixel(x, y)[0].saturating_sub(wide.get_pixel(x, y)[0])])
+    });
+    let strongest = buf.pixels().map(|pixel| pixel[0]).max().unwrap_or(0);
+    if normalize && strongest > 0 {
+        for pixel in buf.pixels_mut() {
+            pixel[0] = (pixel[0] as u32 * 255 / strongest as u32) as u8;
+        }
+    }
+    DynamicImage::ImageLuma8(buf)
+}
+
+// Darkens pixels by up to `strength` with the square of their distance from
+// the center, so the center is untouched and the corners are the darkest.
+pub fn vignette(img: DynamicImage, strength: f32) -> DynamicImage {
+    if strength <= 0.0 {
+        return img;
+    }
+    let keep_alpha = img.color().has_alpha();
+    let mut buf = img.to_rgba8();
+    let (cx, cy) = (buf.width() as f32 / 2.0, buf.height() as f32 / 2.0);
+    let max_distance = cx * cx + cy * cy;
+    for (x, y, pixel) in buf.enumerate_pixels_mut() {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let factor = 1.0 - strength * (dx * dx + dy * dy) / max_distance;
+        for channel in &mut pixel.0[..3] {
+            *channel = (*channel as f32 * factor).round() as u8;
+        }
+    }
+
+    if keep_alpha {
+        DynamicImage::ImageRgba8(buf)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(buf).to_rgb8())
+    }
+}
+
+// Adds a blurred copy of the pixels with luma >= THRESHOLD back onto the
+// image, scaled by INTENSITY, so bright areas glow. The blur radius grows with
+// the image so the effect looks the same at any size.
+pub fn bloom(img: DynamicImage, threshold: u8, intensity: f32) -> DynamicImage {
+    let luma = img.to_luma8();
+    let mut glow = img.to_rgb8();
+    for (pixel, value) in glow.pixels_mut().zip(luma.pixels()) {
+        if value[0] < threshold {
+            *pixel = Rgb([0, 0, 0]);
+        }
+    }
+    let (width, height) = glow.dimensions();
+    let radius = (width.min(height) / 40).max(2);
+    box_blur(&mut glow, width as usize, height as usize, 3, radius as usize);
+    let add = |channel: &mut u8, glow: u8| {
+        *channel = (*channel as f32 + glow as f32 * intensity).round().min(255.0) as u8;
+    };
+    if img.color().has_alpha() {
+        let mut buf = img.to_rgba8();
+        for (pixel, glow) in buf.pixels_mut().zip(glow.pixels()) {
+            pixel.0.iter_mut().zip(glow.0).for_each(|(channel, glow)| add(channel, glow));
+        }
+        DynamicImage::ImageRgba8(buf)
+    } else {
+        let mut buf = img.to_rgb8();
+        for (pixel, glow) in buf.pixels_mut().zip(glow.pixels()) {
+            pixel.0.iter_mut().zip(glow.0).for_each(|(channel, glow)| add(channel, glow));
+        }
+        DynamicImage::ImageRgb8(buf)
+    }
+}
+
+// Output channel `i` takes the value of input channel `order[i]`.
+pub fn swap_channels(img: DynamicImage, order: [usize; 3]) -> DynamicImage {
+    map_rgb(img, |Rgb(rgb)| Rgb(order.map(|channel| rgb[channel])))
+}
+
+pub fn threshold(img: DynamicImage, value: u8) -> DynamicImage {
+    let mut buf = img.to_luma8();
+    for pixel in buf.pixels_mut() {
+        *pixel = if pixel[0] >= value { Luma([255]) } else { Luma([0]) };
+    }
+    DynamicImage::ImageLuma8(buf)
+}
+
+// Turns everything gray except colors within `tolerance` degrees of `hue`.
+// Pixels just outside the range fade to gray over a few degrees so the edge
+// isn't harsh.
+pub fn keep_hue(img: DynamicImage, hue: f32, tolerance: f32) -> DynamicImage {
+    const FADE: f64 = 10.0;
+    map_rgb(img, |pixel| {
+        let (pixel_hue, _, _) = rgb_to_hsv(pixel);
+        let distance = (pixel_hue - hue as f64).rem_euclid(360.0);
+        let distance = distance.min(360.0 - distance);
+        let gray = ((distance - tolerance as f64) / FADE).clamp(0.0, 1.0);
+        let luma = pixel.to_luma()[0] as f64;
+        Rgb(pixel.0.map(|channel| (channel as f64 + (luma - channel as f64) * gray).round() as u8))
+    })
+}
+
+pub fn sepia(img: DynamicImage) -> DynamicImage {
+    map_rgb(img, sepia_tone)
+}
+
+pub fn posterize(img: DynamicImage, levels: u16) -> DynamicImage {
+    let step = 255.0 / (levels.max(2) - 1) as f32;
+    let snap = |value: u8| ((value as f32 / step).round() * step).round() as u8;
+    map_rgb(img, |Rgb([r, g, b])| Rgb([snap(r), snap(g), snap(b)]))
+}
+
+// Reduces the image to at most `colors` colors chosen by median cut, then
+// replaces every pixel with the nearest of them. Alpha is kept as it is.
+pub fn quantize(img: DynamicImage, colors: u16) -> DynamicImage {
+    let palette = quantize_palette(&img, colors);
+    let mut nearest = HashMap::new();
+    map_rgb(img, |Rgb(color)| {
+        Rgb(*nearest.entry(color).or_insert_with(|| {
+            *palette
+                .iter()
+                .min_by_key(|entry| {
+                    entry.iter().zip(color).map(|(&a, b)| (a as i32 - b as i32).pow(2)).sum::<i32>()
+                })
+                .expect("median cut returns at least one color")
+        }))
+    })
+}
+
+// The colors `quantize` picks for the image.
+pub fn quantize_palette(img: &DynamicImage, colors: u16) -> Vec<[u8; 3]> {
+    let mut samples: Vec<[u8; 3]> = img.to_rgb8().pixels().map(|pixel| pixel.0).collect();
+    median_cut(&mut samples, colors.max(1) as usize)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dither {
+    // Snap every pixel to its nearest color
+    #[default]
+    None,
+    // Floyd-Steinberg, pushes each pixel's rounding error onto its neighbours
+    Floyd,
+    // Offsets pixels by an 8x8 Bayer matrix before snapping, giving a regular
+    // cross-hatch pattern
+    Ordered,
+}
+
+impl fmt::Display for Dither {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no variant is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+// The colors `dither` can snap a pixel to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Palette {
+    // LEVELS evenly spaced values per channel, as with `posterize`
+    Levels(u16),
+    // A fixed list such as the one from `quantize_palette`
+    Colors(Vec<[u8; 3]>),
+}
+
+impl Palette {
+    fn nearest(&self, color: [f32; 3]) -> [u8; 3] {
+        match self {
+            Palette::Levels(levels) => {
+                let step = 255.0 / (levels.max(&2) - 1) as f32;
+                color.map(|value| ((value.round() / step).round() * step).round() as u8)
+            }
+            Palette::Colors(colors) => *colors
+                .iter()
+                .min_by(|a, b| distance_sq(a, color).total_cmp(&distance_sq(b, color)))
+                .unwrap_or(&[0, 0, 0]),
+        }
+    }
+
+    // Roughly how far apart neighbouring colors are in any one channel, which
+    // sets the strength of ordered dithering. For a list this is the average
+    // distance from each color to its closest other color.
+    fn spacing(&self) -> f32 {
+        match self {
+            Palette::Levels(levels) => 255.0 / (levels.max(&2) - 1) as f32,
+            Palette::Colors(colors) if colors.len() < 2 => 0.0,
+            Palette::Colors(colors) => {
+                let gap = |a: &[u8; 3], b: &[u8; 3]| a.iter().zip(b).map(|(&a, &b)| a.abs_diff(b)).max().unwrap_or(0);
+                let total: u32 = colors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, a)| {
+                        let others = colors.iter().enumerate().filter(|&(j, _)| j != i);
+                        others.map(|(_, b)| gap(a, b) as u32).min().unwrap_or(0)
+                    })
+                    .sum();
+                total as f32 / colors.len() as f32
+            }
+        }
+    }
+}
+
+fn distance_sq(a: &[u8; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(&a, b)| (a as f32 - b).powi(2)).sum()
+}
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+// Reduces the image to the palette's colors, dithering to hide the banding
+// that snapping smooth gradients causes. Both methods are deterministic and
+// alpha is kept as it is.
+pub fn dither(img: DynamicImage, method: Dither, palette: &Palette) -> DynamicImage {
+    let width = img.width().max(1) as usize;
+    let mut pixels: Vec<[f32; 3]> = img.to_rgb8().pixels().map(|pixel| pixel.0.map(|c| c as f32)).collect();
+    let spacing = palette.spacing();
+    let mut snapped = Vec::with_capacity(pixels.len());
+    for i in 0..pixels.len() {
+        let (x, y) = (i % width, i / width);
+        let color = pixels[i].map(|c| c.clamp(0.0, 255.0));
+        let new = match method {
+            Dither::None | Dither::Floyd => palette.nearest(color),
+            Dither::Ordered => {
+                let offset = ((BAYER_8X8[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5) * spacing;
+                palette.nearest(color.map(|c| c + offset))
+            }
+        };
+        if method == Dither::Floyd {
+            let error: [f32; 3] = std::array::from_fn(|c| color[c] - new[c] as f32);
+            let mut spread = |index: usize, weight: f32| {
+                if let Some(pixel) = pixels.get_mut(index) {
+                    (0..3).for_each(|c| pixel[c] += error[c] * weight);
+                }
+            };
+            if x + 1 < width {
+                spread(i + 1, 7.0 / 16.0);
+                spread(i + width + 1, 1.0 / 16.0);
+            }
+            if x > 0 {
+                spread(i + width - 1, 3.0 / 16.0);
+            }
+            spread(i + width, 5.0 / 16.0);
+        }
+        snapped.push(new);
+    }
+    // map_rgb visits the pixels in the same row-major order.
+    let mut snapped = snapped.into_iter();
+    map_rgb(img, |_| Rgb(snapped.next().expect("one color per pixel")))
+}
+
+// Makes pixels within `tolerance` (Euclidean distance in RGB) of the key color
+// fully transparent. The result is always RGBA.
+pub fn colorkey(img: DynamicImage, r: u8, g: u8, b: u8, tolerance: f32) -> DynamicImage {
+    let mut buf = img.to_rgba8();
+    let key = [r as f32, g as f32, b as f32];
+    for pixel in buf.pixels_mut() {
+        let distance: f32 = (0..3).map(|i| (pixel[i] as f32 - key[i]).powi(2)).sum::<f32>().sqrt();
+        if distance <= tolerance {
+            pixel[3] = 0;
+        }
+    }
+    DynamicImage::ImageRgba8(buf)
+}
+
+// Makes everything outside the largest centered circle transparent. Pixels
+// on the edge are partly transparent so the circle looks smooth.
+pub fn circle_mask(img: DynamicImage) -> DynamicImage {
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let radius = width.min(height) / 2.0;
+    mask_alpha(img, |x, y| radius - (x - width / 2.0).hypot(y - height / 2.0))
+}
+
+// Rounds the corners off with quarter circles of `radius` pixels, capped at
+// half the shorter side.
+pub fn round_corners(img: DynamicImage, radius: u32) -> DynamicImage {
+    if radius == 0 {
+        return img;
+    }
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let radius = (radius as f32).min(width.min(height) / 2.0);
+    mask_alpha(img, |x, y| {
+        let nearest = (x.clamp(radius, width - radius), y.clamp(radius, height - radius));
+        radius - (x - nearest.0).hypot(y - nearest.1)
+    })
+}
+
+// Scales the alpha of each pixel by how much of it lies inside a shape, where
+// `inside` gives the distance from the center of a pixel to the edge of the
+// shape, positive inside.
+fn mask_alpha(img: DynamicImage, inside: impl Fn(f32, f32) -> f32) -> DynamicImage {
+    let mut buf = img.to_rgba8();
+    for (x, y, pixel) in buf.enumerate_pixels_mut() {
+        let coverage = (inside(x as f32 + 0.5, y as f32 + 0.5) + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+    DynamicImage::ImageRgba8(buf)
+}
+
+// Composites the image over a solid `bg` color, dropping the alpha channel.
+pub fn flatten(img: DynamicImage, bg: [u8; 3]) -> DynamicImage {
+    let mut buf = img.to_rgb8();
+    if img.color().has_alpha() {
+        for (pixel, source) in buf.pixels_mut().zip(img.to_rgba8().pixels()) {
+            let alpha = source[3] as f32 / 255.0;
+            for c in 0..3 {
+                pixel[c] = (source[c] as f32 * alpha + bg[c] as f32 * (1.0 - alpha)).round() as u8;
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(buf)
+}
+
+// Draws `top` over `base` with its top-left corner at (x, y). Parts of `top`
+// that fall outside of `base` are clipped.
+pub fn overlay(base: DynamicImage, top: DynamicImage, x: i64, y: i64, opacity: f32) -> DynamicImage {
+    let keep_alpha = base.color().has_alpha();
+    let mut canvas = base.to_rgba8();
+    let mut top = top.to_rgba8();
+    for pixel in top.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+    }
+
+    imageops::overlay(&mut canvas, &top, x, y);
+
+    if keep_alpha {
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+    }
+}
+
+// Covers all of `base` with copies of `top`, `spacing` pixels apart. Each row
+// is shifted by half a step from the one above so the copies run diagonally,
+// and copies crossing an edge are clipped.
+pub fn watermark_tiled(base: DynamicImage, top: DynamicImage, opacity: f32, spacing: u32) -> DynamicImage {
+    let keep_alpha = base.color().has_alpha();
+    let mut canvas = base.to_rgba8();
+    let mut top = top.to_rgba8();
+    for pixel in top.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+    }
+
+    let step_x = (top.width() + spacing).max(1) as i64;
+    let step_y = (top.height() + spacing).max(1) as i64;
+    for (row, y) in (0..).zip((0..canvas.height() as i64).step_by(step_y as usize)) {
+        let shift = row * step_x / 2 % step_x;
+        let mut x = if shift == 0 { 0 } else { shift - step_x };
+        while x < canvas.width() as i64 {
+            imageops::overlay(&mut canvas, &top, x, y);
+            x += step_x;
+        }
+    }
+
+    if keep_alpha {
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+    }
+}
+
+// Repeats `img` in a grid of `rows` by `cols` copies.
+pub fn tile(img: DynamicImage, rows: u32, cols: u32) -> DynamicImage {
+    let keep_alpha = img.color().has_alpha();
+    let (width, height) = (img.width(), img.height());
+    let mut canvas = RgbaImage::new(cols * width, rows * height);
+    let top = img.to_rgba8();
+    for row in 0..rows {
+        for col in 0..cols {
+            imageops::overlay(&mut canvas, &top, (col * width) as i64, (row * height) as i64);
+        }
+    }
+
+    if keep_alpha {
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+    }
+}
+
+// Cuts `img` into `rows` by `cols` pieces, returned row by row. Pieces are
+// the same size except in the last row and column, which also get the pixels
+// left over when the size doesn't divide evenly. `rows` and `cols` are capped
+// at the image size so no piece is empty.
+pub fn split(img: &DynamicImage, rows: u32, cols: u32) -> Vec<DynamicImage> {
+    let rows = rows.clamp(1, img.height().max(1));
+    let cols = cols.clamp(1, img.width().max(1));
+    let (piece_width, piece_height) = (img.width() / cols, img.height() / rows);
+    let mut pieces = Vec::with_capacity((rows * cols) as usize);
+    for row in 0..rows {
+        let height = if row == rows - 1 { img.height() - row * piece_height } else { piece_height };
+        for col in 0..cols {
+            let width = if col == cols - 1 { img.width() - col * piece_width } else { piece_width };
+            pieces.push(img.crop_imm(col * piece_width, row * piece_height, width, height));
+        }
+    }
+    pieces
+}
+
+// Lays `images` out left to right, top to bottom in `cols` columns, each
+// centered in its own cell with `pad` pixels of `bg` between and around the
+// cells. Cells default to the size of the largest image; images that don't fit
+// a fixed `cell` are scaled down to fit.
+pub fn montage(images: &[DynamicImage], cols: u32, pad: u32, bg: [u8; 3], cell: Option<(u32, u32)>) -> DynamicImage {
+    let (cell_width, cell_height) = cell.unwrap_or_else(|| {
+        let width = images.iter().map(DynamicImage::width).max().unwrap_or(0);
+        let height = images.iter().map(DynamicImage::height).max().unwrap_or(0);
+        (width, height)
+    });
+    let count = images.len() as u32;
+    let cols = cols.clamp(1, count.max(1));
+    let rows = count.div_ceil(cols);
+
+    let [r, g, b] = bg;
+    let mut canvas = RgbaImage::from_pixel(
+        cols * cell_width + (cols + 1) * pad,
+        rows * cell_height + (rows + 1) * pad,
+        Rgba([r, g, b, 255]),
+    );
+    for (i, img) in (0..).zip(images) {
+        let img = if img.width() > cell_width || img.height() > cell_height {
+            img.resize(cell_width, cell_height, FilterType::Lanczos3)
+        } else {
+            img.clone()
+        };
+        let x = pad + (i % cols) * (cell_width + pad) + (cell_width - img.width()) / 2;
+        let y = pad + (i / cols) * (cell_height + pad) + (cell_height - img.height()) / 2;
+        imageops::overlay(&mut canvas, &img.to_rgba8(), x as i64, y as i64);
+    }
+
+    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no variant is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+// A grayscale image of one channel. Images without alpha are fully opaque, so
+// their alpha channel comes out all white.
+pub fn extract_channel(img: DynamicImage, channel: Channel) -> DynamicImage {
+    let index = channel as usize;
+    let rgba = img.to_rgba8();
+    DynamicImage::ImageLuma8(GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        Luma([rgba.get_pixel(x, y)[index]])
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorTarget {
+    Luma8,
+    Lumaa8,
+    Rgb8,
+    Rgba8,
+}
+
+impl fmt::Display for ColorTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no variant is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+// Converts to an 8-bit color type. Images that already have the target type
+// are returned as is, and channels gained in the conversion (such as alpha)
+// are filled with their maximum value.
+pub fn convert_color(img: DynamicImage, target: ColorTarget) -> DynamicImage {
+    match (target, img) {
+        (ColorTarget::Luma8, img @ DynamicImage::ImageLuma8(_))
+        | (ColorTarget::Lumaa8, img @ DynamicImage::ImageLumaA8(_))
+        | (ColorTarget::Rgb8, img @ DynamicImage::ImageRgb8(_))
+        | (ColorTarget::Rgba8, img @ DynamicImage::ImageRgba8(_)) => img,
+        (ColorTarget::Luma8, img) => DynamicImage::ImageLuma8(img.to_luma8()),
+        (ColorTarget::Lumaa8, img) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        (ColorTarget::Rgb8, img) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        (ColorTarget::Rgba8, img) => DynamicImage::ImageRgba8(img.to_rgba8()),
+    }
+}
+
+fn median_filter<P>(src: &ImageBuffer<P, Vec<u8>>, radius: u32) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8>,
+{
+    let (width, height) = src.dimensions();
+    let radius = radius as i64;
+    let mut window = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    let mut out: ImageBuffer<P, Vec<u8>> = ImageBuffer::new(width, height);
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let channels = pixel.channels_mut();
+        for (c, channel) in channels.iter_mut().enumerate() {
+            window.clear();
+            for wy in y as i64 - radius..=y as i64 + radius {
+                for wx in x as i64 - radius..=x as i64 + radius {
+                    let wx = wx.clamp(0, width as i64 - 1) as u32;
+                    let wy = wy.clamp(0, height as i64 - 1) as u32;
+                    window.push(src.get_pixel(wx, wy).channels()[c]);
+                }
+            }
+            let middle = window.len() / 2;
+            *channel = *window.select_nth_unstable(middle).1;
+        }
+    }
+    out
+}
+
+fn oil_filter<P>(src: &ImageBuffer<P, Vec<u8>>, radius: u32, intensity: u32) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8>,
+{
+    let (width, height) = src.dimensions();
+    let levels = intensity.clamp(1, 256) as usize;
+    let level_of: Vec<usize> = src
+        .pixels()
+        .map(|pixel| {
+            let rgb = &pixel.channels()[..3];
+            let mean = rgb.iter().map(|&c| c as usize).sum::<usize>() / 3;
+            (mean * levels / 256).min(levels - 1)
+        })
+        .collect();
+
+    let radius = radius as i64;
+    let mut counts = vec![0u32; levels];
+    let mut sums = vec![[0u32; 4]; levels];
+    let mut out: ImageBuffer<P, Vec<u8>> = ImageBuffer::new(width, height);
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        counts.iter_mut().for_each(|count| *count = 0);
+        sums.iter_mut().for_each(|sum| *sum = [0; 4]);
+        for wy in (y as i64 - radius).max(0)..=(y as i64 + radius).min(height as i64 - 1) {
+            for wx in (x as i64 - radius).max(0)..=(x as i64 + radius).min(width as i64 - 1) {
+                let level = level_of[(wy * width as i64 + wx) as usize];
+                counts[level] += 1;
+                for (sum, &channel) in sums[level].iter_mut().zip(src.get_pixel(wx as u32, wy as u32).channels()) {
+                    *sum += channel as u32;
+                }
+            }
+        }
+        let busiest = (0..levels).max_by_key(|&level| counts[level]).unwrap_or(0);
+        let count = counts[busiest].max(1);
+        for (channel, sum) in pixel.channels_mut().iter_mut().zip(sums[busiest]) {
+            *channel = ((sum + count / 2) / count) as u8;
+        }
+    }
+    out
+}
+
+fn pad_buffer<P>(src: &ImageBuffer<P, Vec<u8>>, n: u32, mode: PadMode, fill: P) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8>,
+{
+    let (width, height) = src.dimensions();
+    ImageBuffer::from_fn(width + 2 * n, height + 2 * n, |x, y| {
+        match (pad_source(x as i64 - n as i64, width, mode), pad_source(y as i64 - n as i64, height, mode)) {
+            (Some(sx), Some(sy)) => *src.get_pixel(sx, sy),
+            _ => fill,
+        }
+    })
+}
+
+// The coordinate along an axis of `len` pixels that padding copies into `i`,
+// or None where the fill color goes.
+fn pad_source(i: i64, len: u32, mode: PadMode) -> Option<u32> {
+    let len = len as i64;
+    if (0..len).contains(&i) {
+        return Some(i as u32);
+    }
+    match mode {
+        _ if len == 0 => None,
+        PadMode::Constant => None,
+        PadMode::Edge => Some(i.clamp(0, len - 1) as u32),
+        PadMode::Mirror if len == 1 => Some(0),
+        PadMode::Mirror => {
+            let period = 2 * (len - 1);
+            let i = i.rem_euclid(period);
+            Some(if i < len { i } else { period - i } as u32)
+        }
+    }
+}
+
+fn average_blocks<P>(buf: &mut ImageBuffer<P, Vec<u8>>, block: u32, (x0, y0, x1, y1): (u32, u32, u32, u32))
+where
+    P: Pixel<Subpixel = u8>,
+{
+    for by in (y0..y1).step_by(block as usize) {
+        for bx in (x0..x1).step_by(block as usize) {
+            let (bw, bh) = (block.min(x1 - bx), block.min(y1 - by));
+            let mut sums = [0u64; 4];
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    for (sum, &channel) in sums.iter_mut().zip(buf.get_pixel(x, y).channels()) {
+                        *sum += channel as u64;
+                    }
+                }
+            }
+            let count = (bw * bh) as u64;
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    for (channel, sum) in buf.get_pixel_mut(x, y).channels_mut().iter_mut().zip(sums) {
+                        *channel = ((sum + count / 2) / count) as u8;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Splits the samples into up to `colors` boxes, each time halving the box with
+// the widest channel range at its median, and returns the mean of each box.
+fn median_cut(samples: &mut [[u8; 3]], colors: usize) -> Vec<[u8; 3]> {
+    if samples.is_empty() {
+        return vec![[0, 0, 0]];
+    }
+    // Each box is a range of `samples` with its widest channel and that range.
+    let widest = |samples: &[[u8; 3]]| {
+        (0..3)
+            .map(|channel| {
+                let values = samples.iter().map(|sample| sample[channel]);
+                (values.clone().max().unwrap_or(0) - values.min().unwrap_or(0), channel)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+    let mut boxes = vec![(widest(samples), 0..samples.len())];
+    while boxes.len() < colors {
+        let Some(i) = (0..boxes.len()).filter(|&i| boxes[i].0 .0 > 0).max_by_key(|&i| boxes[i].0) else {
+            break;
+        };
+        let ((_, channel), bounds) = boxes.swap_remove(i);
+        samples[bounds.clone()].sort_unstable_by_key(|sample| sample[channel]);
+        let middle = bounds.start + bounds.len() / 2;
+        for half in [bounds.start..middle, middle..bounds.end] {
+            boxes.push((widest(&samples[half.clone()]), half));
+        }
+    }
+    boxes
+        .into_iter()
+        .map(|(_, bounds)| {
+            let count = bounds.len() as u64;
+            let mut sums = [0u64; 3];
+            for sample in &samples[bounds] {
+                sums.iter_mut().zip(sample).for_each(|(sum, &value)| *sum += value as u64);
+            }
+            sums.map(|sum| ((sum + count / 2) / count) as u8)
+        })
+        .collect()
+}
+
+fn remove_seams(buf: RgbaImage, count: u32, progress: &Progress) -> RgbaImage {
+    let (mut width, height) = (buf.width() as usize, buf.height() as usize);
+    let mut pixels: Vec<[u8; 4]> = buf.pixels().map(|pixel| pixel.0).collect();
+    let mut cost = vec![0u32; width * height];
+    for _ in 0..count {
+        let luma: Vec<i32> = pixels
+            .iter()
+            .map(|[r, g, b, _]| (299 * *r as i32 + 587 * *g as i32 + 114 * *b as i32) / 1000)
+            .collect();
+        let at = |x: usize, y: usize, dx: isize, dy: isize| {
+            let x = x.saturating_add_signed(dx).min(width - 1);
+            let y = y.saturating_add_signed(dy).min(height - 1);
+            luma[y * width + x]
+        };
+        // The cheapest seam ending at each pixel, built top to bottom.
+        for y in 0..height {
+            for x in 0..width {
+                let gx = at(x, y, 1, -1) + 2 * at(x, y, 1, 0) + at(x, y, 1, 1)
+                    - at(x, y, -1, -1) - 2 * at(x, y, -1, 0) - at(x, y, -1, 1);
+                let gy = at(x, y, -1, 1) + 2 * at(x, y, 0, 1) + at(x, y, 1, 1)
+                    - at(x, y, -1, -1) - 2 * at(x, y, 0, -1) - at(x, y, 1, -1);
+                let above = match y {
+                    0 => 0,
+                    _ => cost[(y - 1) * width + x.saturating_sub(1)..(y - 1) * width + (x + 2).min(width)]
+                        .iter()
+                        .copied()
+                        .min()
+                        .unwrap_or(0),
+                };
+                cost[y * width + x] = (gx.unsigned_abs() + gy.unsigned_abs()) + above;
+            }
+        }
+        // Walk back up from the cheapest pixel in the bottom row.
+        let cheapest = |y: usize, from: usize, to: usize| {
+            (from..to).min_by_key(|&x| cost[y * width + x]).expect("seam search range is never empty")
+        };
+        let mut seam = vec![0; height];
+        seam[height - 1] = cheapest(height - 1, 0, width);
+        for y in (0..height - 1).rev() {
+            let x = seam[y + 1];
+            seam[y] = cheapest(y, x.saturating_sub(1), (x + 2).min(width));
+        }
+        pixels = pixels
+            .chunks_exact(width)
+            .zip(&seam)
+            .flat_map(|(row, &x)| row[..x].iter().chain(&row[x + 1..]).copied())
+            .collect();
+        width -= 1;
+        progress.inc();
+    }
+    RgbaImage::from_raw(width as u32, height as u32, pixels.concat()).expect("carved buffer matches its dimensions")
+}
+
+fn box_blur(data: &mut [u8], width: usize, height: usize, channels: usize, radius: usize) {
+    let mut line = Vec::new();
+    for _ in 0..3 {
+        for y in 0..height {
+            blur_line(data, &mut line, y * width * channels, channels, width, channels, radius);
+        }
+        for x in 0..width {
+            blur_line(data, &mut line, x * channels, width * channels, height, channels, radius);
+        }
+    }
+}
+
+// Box blurs the `len` pixels starting at `start` that are `step` bytes apart,
+// using a running sum and repeating the end pixels past either end.
+fn blur_line(data: &mut [u8], line: &mut Vec<u8>, start: usize, step: usize, len: usize, channels: usize, radius: usize) {
+    line.clear();
+    line.extend((0..len).flat_map(|i| &data[start + i * step..start + i * step + channels]));
+    let at = |i: isize, c: usize| line[i.clamp(0, len as isize - 1) as usize * channels + c] as u32;
+    let (radius, window) = (radius as isize, 2 * radius as u32 + 1);
+
+    for c in 0..channels {
+        let mut sum: u32 = (-radius..=radius).map(|i| at(i, c)).sum();
+        for i in 0..len as isize {
+            data[start + i as usize * step + c] = ((sum + window / 2) / window) as u8;
+            sum = sum + at(i + radius + 1, c) - at(i - radius, c);
+        }
+    }
+}
+
+fn sepia_tone(Rgb([r, g, b]): Rgb<u8>) -> Rgb<u8> {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    Rgb([
+        (0.393 * r + 0.769 * g + 0.189 * b).min(255.0) as u8,
+        (0.349 * r + 0.686 * g + 0.168 * b).min(255.0) as u8,
+        (0.272 * r + 0.534 * g + 0.131 * b).min(255.0) as u8,
+    ])
+}
+
+// Applies `f` to the color channels of every pixel, keeping the alpha channel
+// untouched when the image has one.
+fn map_rgb<F>(img: DynamicImage, mut f: F) -> DynamicImage
+where
+    F: FnMut(Rgb<u8>) -> Rgb<u8>,
+{
+    if img.color().has_alpha() {
+        let mut buf = img.to_rgba8();
+        for pixel in buf.pixels_mut() {
+            let Rgb([r, g, b]) = f(Rgb([pixel[0], pixel[1], pixel[2]]));
+            pixel.0 = [r, g, b, pixel[3]];
+        }
+        DynamicImage::ImageRgba8(buf)
+    } else {
+        let mut buf = img.to_rgb8();
+        for pixel in buf.pixels_mut() {
+            *pixel = f(*pixel);
+        }
+        DynamicImage::ImageRgb8(buf)
+    }
+}
@@ -0,0 +1,12 @@
+use image::{DynamicImage, GrayAlphaImage, GrayImage, Luma, LumaA};
+use mirage::ascii::to_ascii;
+
+#[test]
+fn ascii_maps_brightness_to_the_ramp_and_halves_the_rows() {
+    let img = DynamicImage::ImageLuma8(GrayImage::from_fn(4, 8, |x, _| Luma([[0, 100, 200, 255][x as usize]])));
+    assert_eq!(to_ascii(&img, 4, false), " =#@\n =#@\n =#@\n =#@\n");
+    assert_eq!(to_ascii(&img, 4, true), "@+: \n@+: \n@+: \n@+: \n");
+
+    let transparent = DynamicImage::ImageLumaA8(GrayAlphaImage::from_pixel(2, 4, LumaA([255, 0])));
+    assert_eq!(to_ascii(&transparent, 2, false), "  \n  \n");
+}
@@ -0,0 +1,30 @@
+use std::fs;
+use std::process::Command;
+
+use image::{Rgb, RgbImage};
+
+#[test]
+fn batch_writes_every_file_in_parallel() {
+    let dir = std::env::temp_dir().join(format!("mirage-batch-{}", std::process::id()));
+    let (indir, outdir) = (dir.join("in"), dir.join("out"));
+    fs::create_dir_all(&indir).unwrap();
+    for i in 0..6u8 {
+        let img = RgbImage::from_fn(16 + i as u32, 8, |x, y| Rgb([x as u8 * 10, y as u8 * 20, i * 40]));
+        img.save(indir.join(format!("{}.png", i))).unwrap();
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_mirage"))
+        .args(["--quiet", "--invert", "batch", "--jobs", "3"])
+        .arg(&indir)
+        .arg(&outdir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    for i in 0..6u8 {
+        let img = image::open(outdir.join(format!("{}.png", i))).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (16 + i as u32, 8));
+        assert_eq!(img.get_pixel(1, 1).0, [245, 235, 255 - i * 40]);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
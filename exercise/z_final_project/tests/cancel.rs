@@ -0,0 +1,29 @@
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+#[test]
+fn ctrl_c_stops_a_render_without_writing_it() {
+    let outfile = std::env::temp_dir().join(format!("mirage-cancel-{}.png", std::process::id()));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mirage"))
+        .args(["--verbose", "mandelbrot", "--width", "8000", "--height", "8000", "--max-iter", "5000"])
+        .arg(&outfile)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The first progress line means the handler is installed and rendering started.
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    assert!(line.starts_with("Rendering"), "unexpected output: {}", line);
+    let killed = Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    assert!(killed.success());
+
+    let mut rest = String::new();
+    while stderr.read_line(&mut rest).unwrap() > 0 {}
+    assert_eq!(child.wait().unwrap().code(), Some(130));
+    assert!(rest.contains("Interrupted"), "unexpected output: {}", rest);
+    assert!(!outfile.exists());
+}
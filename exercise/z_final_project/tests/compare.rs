@@ -0,0 +1,25 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use mirage::compare::{compare, diff_image};
+
+fn gray(width: u32, height: u32, value: u8) -> RgbImage {
+    RgbImage::from_pixel(width, height, Rgb([value; 3]))
+}
+
+#[test]
+fn compare_reports_max_mean_and_differing_pixels() {
+    let a = DynamicImage::ImageRgb8(gray(2, 2, 100));
+    let mut b = gray(2, 2, 100);
+    b.put_pixel(1, 0, Rgb([100, 120, 100]));
+    let report = compare(&a, &DynamicImage::ImageRgb8(b)).unwrap();
+    assert_eq!(report.max, 20);
+    assert_eq!(report.mean, 5.0);
+    assert_eq!(report.differing_percent(), 25.0);
+}
+
+#[test]
+fn compare_rejects_different_dimensions() {
+    let a = DynamicImage::ImageRgb8(gray(2, 2, 0));
+    let b = DynamicImage::ImageRgb8(gray(2, 3, 0));
+    assert!(compare(&a, &b).is_err());
+    assert!(diff_image(&a, &b).is_err());
+}
@@ -0,0 +1,46 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use mirage::fetch::{fetch, is_url, set_retries, set_timeout, FetchError};
+
+// Answers one connection per entry in `responses`, in order. A `None` entry
+// reads the request and then never answers, to test timeouts.
+fn serve(responses: Vec<Option<(&'static str, &'static [u8])>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/image.png", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for (response, stream) in responses.into_iter().zip(listener.incoming()) {
+            let mut stream = stream.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            match response {
+                Some((status, body)) => {
+                    let head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len());
+                    stream.write_all(head.as_bytes()).unwrap();
+                    stream.write_all(body).unwrap();
+                }
+                None => thread::sleep(std::time::Duration::from_secs(5)),
+            }
+        }
+    });
+    url
+}
+
+// One test so the global timeout and retry settings don't race.
+#[test]
+fn fetch_retries_server_errors_but_not_client_errors_or_timeouts() {
+    assert!(is_url("https://example.com/a.png") && !is_url("http.png"));
+    set_timeout(1);
+
+    set_retries(1);
+    let url = serve(vec![Some(("503 Service Unavailable", b"")), Some(("200 OK", b"pixels"))]);
+    assert_eq!(fetch(&url).unwrap(), b"pixels");
+
+    let url = serve(vec![Some(("404 Not Found", b"")), Some(("200 OK", b"pixels"))]);
+    assert!(matches!(fetch(&url), Err(FetchError::Status(404))));
+
+    set_retries(0);
+    let url = serve(vec![None]);
+    assert!(matches!(fetch(&url), Err(FetchError::Transport(_))));
+}
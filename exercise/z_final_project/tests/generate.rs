@@ -0,0 +1,64 @@
+use mirage::color::{colormap, Colormap};
+use mirage::generate::{fractal, fractal_grid, mandelbrot, plasma};
+use num_complex::Complex;
+
+#[test]
+fn plasma_is_deterministic_for_a_seed() {
+    assert_eq!(plasma(16.0, 7, 32, 24), plasma(16.0, 7, 32, 24));
+    assert_ne!(plasma(16.0, 7, 32, 24), plasma(16.0, 8, 32, 24));
+}
+
+#[test]
+fn fractal_grid_places_each_cell() {
+    let grid = fractal_grid(2, 3, 10, 8, Colormap::Classic).to_rgb8();
+    assert_eq!(grid.dimensions(), (30, 16));
+    // The last cell is rendered with the constant at 5/6 of a turn.
+    let c = Complex::from_polar(0.7885f32, std::f32::consts::TAU * 5.0 / 6.0);
+    let cell = fractal(c.re, c.im, 255, 10, 8, Colormap::Classic).to_rgb8();
+    assert_eq!(grid.get_pixel(23, 12), cell.get_pixel(3, 4));
+}
+
+#[test]
+fn colormaps_run_from_dark_to_bright() {
+    for scheme in [Colormap::Grayscale, Colormap::Fire, Colormap::Ocean] {
+        assert_eq!(colormap(0.0, scheme).0, [0, 0, 0], "{:?}", scheme);
+        assert_eq!(colormap(1.0, scheme).0, [255, 255, 255], "{:?}", scheme);
+    }
+    assert_eq!(colormap(0.5, Colormap::Grayscale).0, [128, 128, 128]);
+}
+
+#[test]
+fn mandelbrot_matches_a_serial_render() {
+    let (width, height, max_iter) = (48u32, 32u32, 64u32);
+    let (center, zoom) = ((-0.5, 0.1), 1.5);
+    let image = mandelbrot(width, height, max_iter, center, zoom, Colormap::Fire).to_rgb8();
+
+    let scale = 3.0 / (zoom * width.min(height) as f64);
+    for y in 0..height {
+        for x in 0..width {
+            let c = Complex::new(
+                center.0 + (x as f64 - width as f64 / 2.0) * scale,
+                center.1 + (y as f64 - height as f64 / 2.0) * scale,
+            );
+            let mut z = Complex::new(0.0, 0.0);
+            let mut i = 0;
+            while i < max_iter && z.norm_sqr() <= 4.0 {
+                z = z * z + c;
+                i += 1;
+            }
+            let expected = if i >= max_iter {
+                [0, 0, 0]
+            } else {
+                let smooth = i as f64 + 1.0 - z.norm().ln().ln() / std::f64::consts::LN_2;
+                colormap((smooth / max_iter as f64) as f32, Colormap::Fire).0
+            };
+            assert_eq!(image.get_pixel(x, y).0, expected, "pixel ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn mandelbrot_renders_large_images() {
+    let image = mandelbrot(2000, 2000, 16, (-0.5, 0.0), 1.0, Colormap::Classic);
+    assert_eq!((image.width(), image.height()), (2000, 2000));
+}
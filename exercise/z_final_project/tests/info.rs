@@ -0,0 +1,47 @@
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+use mirage::info::{dominant_colors, hash_image, hex_color, histogram, histogram_csv, stats};
+
+#[test]
+fn hash_covers_only_the_pixel_bytes() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([0, 0, 0])));
+    // sha256 of three zero bytes.
+    assert_eq!(hash_image(&img), "709e80c88487a2411e1ee4dfb9f22a861492d20c4765150c0c794abd70f8147c");
+}
+
+#[test]
+fn histogram_counts_each_channel() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 1, |x, _| Rgb([x as u8, 7, 255])));
+    let hist = histogram(&img);
+    assert_eq!(&hist[0][..5], &[1, 1, 1, 1, 0]);
+    assert_eq!(hist[1][7], 4);
+    assert_eq!(hist[2][255], 4);
+    assert_eq!(histogram_csv(&hist).lines().nth(8), Some("7,0,4,0"));
+}
+
+#[test]
+fn stats_summarize_each_channel() {
+    let alpha = [0, 255, 255, 70];
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(4, 1, |x, _| Rgba([x as u8 * 2, 9, 255, alpha[x as usize]])));
+    let summary = stats(&img);
+    assert_eq!((summary.r.min, summary.r.max), (0, 6));
+    assert_eq!(summary.r.mean, 3.0);
+    assert!((summary.r.stddev - 5f64.sqrt()).abs() < 1e-9);
+    assert_eq!((summary.g.mean, summary.g.stddev), (9.0, 0.0));
+    assert_eq!((summary.transparent, summary.opaque), (1, 2));
+
+    let opaque = stats(&DynamicImage::ImageRgb8(RgbImage::new(3, 2)));
+    assert_eq!((opaque.a.min, opaque.transparent, opaque.opaque), (255, 0, 6));
+}
+
+#[test]
+fn dominant_colors_are_ordered_by_population() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(10, 10, |x, y| match (x, y) {
+        (0..=5, _) => Rgba([200, 10, 10, 255]),
+        (_, 0..=6) => Rgba([10, 10, 200, 255]),
+        _ => Rgba([0, 255, 0, 0]),
+    }));
+    let colors = dominant_colors(&img, 3);
+    assert_eq!(colors, vec![Rgb([200, 10, 10]), Rgb([10, 10, 200])]);
+    assert_eq!(hex_color(colors[1]), "#0a0ac8");
+    assert_eq!(dominant_colors(&img, 1).len(), 1);
+}
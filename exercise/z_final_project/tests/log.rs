@@ -0,0 +1,46 @@
+use std::fs;
+use std::process::Command;
+
+use image::RgbImage;
+use mirage::log::{render, Level, LogFormat};
+
+#[test]
+fn text_records_are_prefixed_by_level() {
+    assert_eq!(render(Level::Info, None, "file 1/2: a.png", LogFormat::Text), "file 1/2: a.png");
+    assert_eq!(render(Level::Warning, None, "lossy", LogFormat::Text), "Warning: lossy");
+    assert_eq!(render(Level::Error, Some("load"), "missing", LogFormat::Text), "Error: missing");
+}
+
+#[test]
+fn json_records_carry_kind_and_message() {
+    let line = render(Level::Error, Some("load"), "can't open \"a.png\"", LogFormat::Json);
+    let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(record["level"], "error");
+    assert_eq!(record["kind"], "load");
+    assert_eq!(record["message"], "can't open \"a.png\"");
+
+    let line = render(Level::Warning, None, "lossy", LogFormat::Json);
+    assert_eq!(line, r#"{"level":"warning","message":"lossy"}"#);
+}
+
+#[test]
+fn timings_report_each_step_and_the_total() {
+    let dir = std::env::temp_dir().join(format!("mirage-timings-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (infile, outfile) = (dir.join("in.png"), dir.join("out.png"));
+    RgbImage::new(4, 4).save(&infile).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mirage"))
+        .args(["--timings", "--invert", "--blur", "1.5", "transform"])
+        .args([&infile, &outfile])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let labels: Vec<&str> = stderr.lines().map(|line| line.rsplit_once(": ").unwrap().0).collect();
+    assert_eq!(
+        labels,
+        [format!("load {}", infile.display()), "blur(1.5)".into(), "invert".into(), format!("save {}", outfile.display()), "total".into()]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
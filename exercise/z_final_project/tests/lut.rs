@@ -0,0 +1,23 @@
+use mirage::lut::parse_lut;
+
+fn row(f: impl Fn(usize) -> usize) -> String {
+    (0..256).map(|i| f(i).to_string()).collect::<Vec<_>>().join(",")
+}
+
+#[test]
+fn lut_reads_three_rows_skipping_comments() {
+    let text = format!("# invert red\n{}\n\n{}\n{}\n", row(|i| 255 - i), row(|i| i), row(|_| 0));
+    let table = parse_lut(&text).unwrap();
+    assert_eq!(table[0][0], 255);
+    assert_eq!(table[1][200], 200);
+    assert_eq!(table[2][255], 0);
+}
+
+#[test]
+fn lut_errors_name_the_line() {
+    let short = format!("{}\n{}\n1,2,3\n", row(|i| i), row(|i| i));
+    assert_eq!(parse_lut(&short).unwrap_err(), "line 3: expected 256 values, found 3");
+    let bad = format!("{}\n{}\n{}\n", row(|i| i), row(|i| i + 1), row(|i| i));
+    assert!(parse_lut(&bad).unwrap_err().starts_with("line 2: invalid value \"256\""));
+    assert!(parse_lut(&row(|i| i)).is_err());
+}
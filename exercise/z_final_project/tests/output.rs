@@ -0,0 +1,49 @@
+use std::fs;
+use std::process::Command;
+
+use image::io::Reader as ImageReader;
+use image::{ImageFormat, Rgb, RgbImage};
+
+fn mirage(args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_mirage")).args(args).status().unwrap();
+    assert!(status.success(), "mirage {:?} failed", args);
+}
+
+#[test]
+fn outfiles_without_an_extension_keep_the_input_format() {
+    let dir = std::env::temp_dir().join(format!("mirage-output-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let infile = dir.join("in.jpg");
+    RgbImage::from_pixel(8, 8, Rgb([90, 120, 150])).save(&infile).unwrap();
+
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    mirage(&["--invert", "transform", &path("in.jpg"), &path("copy")]);
+    mirage(&["solid", &path("generated"), "--width", "4", "--height", "4"]);
+    mirage(&["--format", "bmp", "transform", &path("in.jpg"), &path("converted")]);
+
+    let format = |name: &str| ImageReader::open(dir.join(name)).unwrap().with_guessed_format().unwrap().format();
+    assert_eq!(format("copy.jpg"), Some(ImageFormat::Jpeg));
+    assert_eq!(format("generated.png"), Some(ImageFormat::Png));
+    assert_eq!(format("converted.bmp"), Some(ImageFormat::Bmp));
+    assert!(!dir.join("copy").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn transform_writes_every_out_file_in_its_own_format() {
+    let dir = std::env::temp_dir().join(format!("mirage-outputs-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    RgbImage::from_pixel(8, 8, Rgb([90, 120, 150])).save(dir.join("in.png")).unwrap();
+
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    mirage(&["--quality", "50", "--invert", "transform", &path("in.png"), &path("a.png"), "--out", &path("b.jpg")]);
+    mirage(&["transform", &path("in.png"), "--out", &path("c.bmp"), "--out", &path("d.png")]);
+
+    let format = |name: &str| ImageReader::open(dir.join(name)).unwrap().with_guessed_format().unwrap().format();
+    assert_eq!(format("a.png"), Some(ImageFormat::Png));
+    assert_eq!(format("b.jpg"), Some(ImageFormat::Jpeg));
+    assert_eq!(format("c.bmp"), Some(ImageFormat::Bmp));
+    assert_eq!(format("d.png"), Some(ImageFormat::Png));
+    assert_eq!(image::open(dir.join("a.png")).unwrap().to_rgb8().get_pixel(0, 0).0, [165, 135, 105]);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use mirage::parse::{parse_aspect, parse_crop_pct, parse_geometry, parse_manifest, parse_pad};
+use mirage::transform::PadMode;
+
+#[test]
+fn manifest_skips_blank_lines_and_comments() {
+    let pairs = parse_manifest("# inputs\na.png\tout/a.jpg\n\n  \nb.png\tout/b.jpg\r\n").unwrap();
+    assert_eq!(
+        pairs,
+        vec![
+            (PathBuf::from("a.png"), PathBuf::from("out/a.jpg")),
+            (PathBuf::from("b.png"), PathBuf::from("out/b.jpg")),
+        ]
+    );
+}
+
+#[test]
+fn manifest_errors_name_the_line() {
+    let err = parse_manifest("a.png\tout/a.png\n# comment\nb.png out/b.png\n").unwrap_err();
+    assert!(err.starts_with("line 3:"), "{}", err);
+}
+
+#[test]
+fn geometry_offsets_are_optional() {
+    assert_eq!(parse_geometry("640x480+10+20"), Ok((10, 20, 640, 480)));
+    assert_eq!(parse_geometry("640x480"), Ok((0, 0, 640, 480)));
+    assert!(parse_geometry("640x480+10").is_err());
+    assert!(parse_geometry("10,20,640,480").is_err());
+}
+
+#[test]
+fn aspect_rejects_zero_and_negative_components() {
+    assert_eq!(parse_aspect("16:9"), Ok((16, 9)));
+    assert!(parse_aspect("0:9").is_err());
+    assert!(parse_aspect("16:-9").is_err());
+    assert!(parse_aspect("16x9").is_err());
+}
+
+#[test]
+fn pad_color_is_only_for_constant_mode() {
+    assert_eq!(parse_pad("8,mirror"), Ok((8, PadMode::Mirror, [0, 0, 0])));
+    assert_eq!(parse_pad("8, Constant, 255,0,0"), Ok((8, PadMode::Constant, [255, 0, 0])));
+    assert!(parse_pad("8,edge,255,0,0").is_err());
+    assert!(parse_pad("8,wrap").is_err());
+    assert!(parse_pad("8").is_err());
+}
+
+#[test]
+fn crop_pct_rejects_empty_regions() {
+    assert!(parse_crop_pct("0%,0%,0%,50%").is_err());
+    assert!(parse_crop_pct("0%,0%,50%,0%").is_err());
+    assert_eq!(parse_crop_pct("10%,20%,30%,40%"), Ok((10.0, 20.0, 30.0, 40.0)));
+}
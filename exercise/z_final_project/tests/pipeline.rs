@@ -0,0 +1,20 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use mirage::pipeline::{apply_pipeline_in_region, Operation};
+
+#[test]
+fn region_limits_the_operations() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(6, 4, Rgb([10, 20, 30])));
+    let img = apply_pipeline_in_region(img, &[Operation::Invert], (4, 2, 10, 10)).to_rgb8();
+    assert_eq!(img.dimensions(), (6, 4));
+    assert_eq!(img.get_pixel(3, 3).0, [10, 20, 30]);
+    assert_eq!(img.get_pixel(4, 2).0, [245, 235, 225]);
+    assert_eq!(img.get_pixel(5, 3).0, [245, 235, 225]);
+}
+
+#[test]
+fn only_size_changing_operations_are_rejected_for_regions() {
+    assert!(Operation::Blur(1.0).keeps_dimensions());
+    assert!(Operation::Rotate(180).keeps_dimensions());
+    assert!(!Operation::Rotate(90).keeps_dimensions());
+    assert!(!Operation::Resize { width: 2, height: 2, keep_aspect: false }.keeps_dimensions());
+}
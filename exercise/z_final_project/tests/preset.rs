@@ -0,0 +1,69 @@
+use mirage::pipeline::Operation;
+use mirage::preset::parse_preset;
+
+#[test]
+fn preset_operations_keep_their_order() {
+    let preset = parse_preset(
+        r#"
+        [[op]]
+        kind = "blur"
+        sigma = 2.0
+
+        [[op]]
+        kind = "resize"
+        width = 10
+        height = 20
+
+        [[op]]
+        kind = "grayscale"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        preset.operations,
+        vec![
+            Operation::Blur(2.0),
+            Operation::Resize { width: 10, height: 20, keep_aspect: false },
+            Operation::Grayscale,
+        ]
+    );
+}
+
+#[test]
+fn preset_rejects_unknown_kinds() {
+    assert!(parse_preset("[[op]]\nkind = \"melt\"\n").is_err());
+}
+
+#[test]
+fn preset_rejects_negative_colorkey_tolerance() {
+    let preset = "[[op]]\nkind = \"colorkey\"\nr = 0\ng = 255\nb = 0\ntolerance = -1.0\n";
+    assert!(parse_preset(preset).is_err());
+}
+
+#[test]
+fn preset_rejects_non_finite_scale_factors() {
+    for factor in ["inf", "nan"] {
+        let preset = format!("[[op]]\nkind = \"scale\"\nfactor = {}\n", factor);
+        assert!(parse_preset(&preset).is_err(), "{}", factor);
+    }
+}
+
+#[test]
+fn preset_rejects_infinite_dog_sigmas() {
+    assert!(parse_preset("[[op]]\nkind = \"dog\"\nsigma1 = 1.0\nsigma2 = inf\n").is_err());
+    assert!(parse_preset("[[op]]\nkind = \"dog\"\nsigma1 = 1.0\nsigma2 = 3.0\n").is_ok());
+}
+
+#[test]
+fn preset_rejects_non_finite_gamma() {
+    for value in ["inf", "nan"] {
+        let preset = format!("[[op]]\nkind = \"gamma\"\nvalue = {}\n", value);
+        assert!(parse_preset(&preset).is_err(), "{}", value);
+    }
+}
+
+#[test]
+fn preset_rejects_empty_crop_pct_regions() {
+    let preset = "[[op]]\nkind = \"crop-pct\"\nx = 0.0\ny = 0.0\nwidth = 0.0\nheight = 50.0\n";
+    assert!(parse_preset(preset).is_err());
+}
@@ -0,0 +1,584 @@
+use std::collections::HashSet;
+
+use image::{
+    DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Rgb, RgbImage, Rgba, RgbaImage,
+};
+use mirage::generate::checkerboard;
+use mirage::transform::{
+    auto_contrast, bloom, blur, border, brighten, circle_mask, colorkey, convert_color, crop,
+    crop_aspect, crop_pct, deskew, difference_of_gaussians, dither, edges, extract_channel,
+    fast_blur, flatten, fliph, flipv, gamma, grayscale, invert, keep_hue, levels, median, montage,
+    oil_paint, pad, pixel_resize, pixelate, posterize, quantize, quantize_palette, rotate,
+    rotate_arbitrary, round_corners, saturate, scale, seam_carve, sharpen, skew_angle, split,
+    swap_channels, temperature, threshold, thumbnail, tile, trim, trim_bounds, vignette,
+    watermark_tiled, Channel, ColorTarget, Dither, PadMode, Palette,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// A small image where every pixel is different, so flips and rotations can't
+// accidentally produce the original.
+fn sample() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(5, 3, |x, y| {
+        Rgb([(x * 50) as u8, (y * 80) as u8, (x * 20 + y * 30) as u8])
+    }))
+}
+
+#[test]
+fn blur_keeps_dimensions() {
+    let img = blur(sample(), 1.5);
+    assert_eq!(img.dimensions(), (5, 3));
+}
+
+#[test]
+fn sharpen_steepens_edges() {
+    let step = GrayImage::from_fn(16, 4, |x, _| Luma([if x < 8 { 100 } else { 150 }]));
+    let step = DynamicImage::ImageLuma8(step);
+    let contrast = |img: &GrayImage| img.get_pixel(8, 2)[0] as i32 - img.get_pixel(7, 2)[0] as i32;
+    let before = contrast(&step.to_luma8());
+    let after = contrast(&sharpen(step, 1.0, 0).to_luma8());
+    assert!(after > before, "{} should exceed {}", after, before);
+}
+
+#[test]
+fn brighten_raises_every_channel() {
+    let original = sample().to_rgb8();
+    let brightened = brighten(sample(), 10).to_rgb8();
+    for (before, after) in original.pixels().zip(brightened.pixels()) {
+        for channel in 0..3 {
+            assert_eq!(after[channel], before[channel].saturating_add(10));
+        }
+    }
+}
+
+#[test]
+fn crop_returns_requested_size() {
+    let img = crop(sample(), 1, 1, 3, 2);
+    assert_eq!(img.dimensions(), (3, 2));
+    assert_eq!(img.to_rgb8().get_pixel(0, 0), sample().to_rgb8().get_pixel(1, 1));
+}
+
+#[test]
+fn rotate90_four_times_is_identity() {
+    let img = (0..4).fold(sample(), |img, _| rotate(img, 90));
+    assert_eq!(img, sample());
+}
+
+#[test]
+fn rotate90_swaps_dimensions() {
+    assert_eq!(rotate(sample(), 90).dimensions(), (3, 5));
+}
+
+#[test]
+fn invert_twice_is_identity() {
+    assert_eq!(invert(invert(sample())), sample());
+}
+
+#[test]
+fn grayscale_has_equal_channels() {
+    let img = grayscale(sample()).to_rgb8();
+    for pixel in img.pixels() {
+        assert_eq!(pixel[0], pixel[1]);
+        assert_eq!(pixel[1], pixel[2]);
+    }
+}
+
+#[test]
+fn saturate_zero_matches_grayscale() {
+    let desaturated = saturate(sample(), 0.0).to_rgb8();
+    let gray = grayscale(sample()).to_rgb8();
+    for (a, b) in desaturated.pixels().zip(gray.pixels()) {
+        for channel in 0..3 {
+            assert!(a[channel].abs_diff(b[channel]) <= 1, "{:?} vs {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn fliph_and_flipv_equal_rotate180() {
+    assert_eq!(flipv(fliph(sample())), rotate(sample(), 180));
+}
+
+#[test]
+fn posterize_with_256_levels_is_identity() {
+    assert_eq!(posterize(sample(), 256), sample());
+}
+
+#[test]
+fn levels_with_zero_deltas_is_identity() {
+    assert_eq!(levels(sample(), 0, 0, 0), sample());
+}
+
+#[test]
+fn levels_saturate_at_255() {
+    let img = levels(sample(), 300, 300, 300).to_rgb8();
+    assert!(img.pixels().all(|pixel| pixel.0 == [255, 255, 255]));
+}
+
+#[test]
+fn threshold_extremes() {
+    let white = threshold(sample(), 0);
+    let white = white.as_luma8().expect("threshold should produce a luma image");
+    assert!(white.pixels().all(|pixel| pixel[0] == 255));
+
+    let mut img = sample().to_rgb8();
+    img.put_pixel(2, 1, Rgb([255, 255, 255]));
+    let black = threshold(DynamicImage::ImageRgb8(img), 255);
+    let black = black.as_luma8().expect("threshold should produce a luma image");
+    for (x, y, pixel) in black.enumerate_pixels() {
+        assert_eq!(pixel[0], if (x, y) == (2, 1) { 255 } else { 0 }, "pixel ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn edges_respond_at_checkerboard_boundaries() {
+    let img = edges(checkerboard(8, 32, 32, [0, 0, 0], [255, 255, 255]));
+    let img = img.as_luma8().expect("edges should produce a luma image");
+    // Pixels either side of a cell boundary see the full black/white step,
+    // while the middle of a cell is flat.
+    assert_eq!(img.get_pixel(7, 4)[0], 255);
+    assert_eq!(img.get_pixel(8, 4)[0], 255);
+    assert_eq!(img.get_pixel(4, 4)[0], 0);
+}
+
+#[test]
+fn convert_color_to_matching_type_is_identity() {
+    assert_eq!(convert_color(sample(), ColorTarget::Rgb8), sample());
+}
+
+#[test]
+fn convert_color_rgb_to_rgba_is_opaque() {
+    let img = convert_color(sample(), ColorTarget::Rgba8);
+    let img = img.as_rgba8().expect("conversion should produce an rgba image");
+    assert!(img.pixels().all(|pixel| pixel[3] == 255));
+}
+
+#[test]
+fn tile_repeats_the_image() {
+    let img = tile(sample(), 2, 3).to_rgb8();
+    assert_eq!(img.dimensions(), (15, 6));
+    assert_eq!(img.get_pixel(12, 4), sample().to_rgb8().get_pixel(2, 1));
+}
+
+#[test]
+fn watermark_tiled_staggers_rows_and_clips_edges() {
+    let base = DynamicImage::ImageRgb8(RgbImage::from_pixel(10, 7, Rgb([0, 0, 0])));
+    let mark = DynamicImage::ImageRgb8(RgbImage::from_pixel(3, 2, Rgb([255, 255, 255])));
+    let img = watermark_tiled(base, mark, 1.0, 1).to_rgb8();
+    assert_eq!(img.dimensions(), (10, 7));
+    // Copies every 4 pixels across and 3 down, every other row shifted by 2.
+    assert_eq!(img.get_pixel(0, 0).0, [255, 255, 255]);
+    assert_eq!(img.get_pixel(3, 0).0, [0, 0, 0]);
+    assert_eq!(img.get_pixel(5, 4).0, [0, 0, 0]);
+    assert_eq!(img.get_pixel(8, 4).0, [255, 255, 255]);
+    assert_eq!(img.get_pixel(7, 6).0, [0, 0, 0]);
+    // Copies crossing the left and right edges are clipped rather than skipped.
+    assert_eq!(img.get_pixel(9, 1).0, [255, 255, 255]);
+    assert_eq!(img.get_pixel(0, 3).0, [255, 255, 255]);
+    assert_eq!(img.get_pixel(1, 3).0, [0, 0, 0]);
+}
+
+#[test]
+fn pixel_resize_doubles_pixels_without_blending() {
+    let board = checkerboard(1, 4, 4, [0, 0, 0], [255, 255, 255]);
+    let img = pixel_resize(board.clone(), 8, 8).to_rgb8();
+    let board = board.to_rgb8();
+    assert_eq!(img.dimensions(), (8, 8));
+    for (x, y, pixel) in img.enumerate_pixels() {
+        assert_eq!(pixel, board.get_pixel(x / 2, y / 2));
+    }
+}
+
+#[test]
+fn split_gives_the_remainder_to_the_last_row_and_column() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(7, 5, |x, y| Rgb([x as u8, y as u8, 0])));
+    let pieces = split(&img, 2, 3);
+    let sizes: Vec<(u32, u32)> = pieces.iter().map(|piece| (piece.width(), piece.height())).collect();
+    assert_eq!(sizes, [(2, 2), (2, 2), (3, 2), (2, 3), (2, 3), (3, 3)]);
+    // The last piece starts at (4, 2).
+    assert_eq!(pieces[5].to_rgb8().get_pixel(0, 0).0, [4, 2, 0]);
+    assert_eq!(split(&img, 10, 10).len(), 35);
+}
+
+// Dark horizontal rules on white, like lines of text on a page.
+fn ruled_page() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(300, 200, |x, y| {
+        let ruled = (20..280).contains(&x) && y % 25 < 3;
+        if ruled { Rgb([20, 20, 20]) } else { Rgb([255, 255, 255]) }
+    }))
+}
+
+#[test]
+fn skew_angle_finds_the_rotation_of_a_page() {
+    let page = ruled_page();
+    assert_eq!(skew_angle(&page, 10.0), Some(0.0));
+    for degrees in [3.5, -6.0] {
+        let skewed = rotate_arbitrary(page.clone(), degrees, [255; 4], false);
+        let found = skew_angle(&skewed, 10.0).unwrap();
+        assert!((found - degrees).abs() <= 0.2, "found {} for {}", found, degrees);
+    }
+    // Out of range of the search.
+    let skewed = rotate_arbitrary(page, 8.0, [255; 4], false);
+    assert!(skew_angle(&skewed, 2.0).is_none_or(|found| found.abs() <= 2.0));
+}
+
+#[test]
+fn deskew_leaves_images_without_lines_alone() {
+    let blank = DynamicImage::ImageRgb8(RgbImage::from_pixel(50, 40, Rgb([200, 200, 200])));
+    assert_eq!(skew_angle(&blank, 10.0), None);
+    assert_eq!(deskew(blank.clone(), 10.0), blank);
+
+    let straightened = deskew(rotate_arbitrary(ruled_page(), 4.0, [255; 4], false), 10.0);
+    assert_eq!(skew_angle(&straightened, 10.0), Some(0.0));
+}
+
+#[test]
+fn circle_mask_clears_the_corners_and_keeps_the_center() {
+    let img = circle_mask(sample()).to_rgba8();
+    assert_eq!(img.dimensions(), (5, 3));
+    assert_eq!(img.get_pixel(2, 1)[3], 255);
+    assert_eq!(img.get_pixel(0, 0)[3], 0);
+    assert_eq!(img.get_pixel(4, 2)[3], 0);
+    assert_eq!(img.get_pixel(2, 1).0[..3], sample().to_rgb8().get_pixel(2, 1).0);
+
+    let img = circle_mask(DynamicImage::ImageRgb8(RgbImage::new(40, 40))).to_rgba8();
+    // The edge is anti-aliased rather than cut off.
+    assert!(img.pixels().any(|pixel| pixel[3] > 0 && pixel[3] < 255));
+}
+
+#[test]
+fn round_corners_only_touches_the_corners() {
+    let img = round_corners(DynamicImage::ImageRgb8(RgbImage::new(20, 10)), 4).to_rgba8();
+    assert_eq!(img.get_pixel(0, 0)[3], 0);
+    assert_eq!(img.get_pixel(19, 9)[3], 0);
+    assert_eq!(img.get_pixel(0, 5)[3], 255);
+    assert_eq!(img.get_pixel(10, 0)[3], 255);
+    assert_eq!(img.get_pixel(2, 2)[3], 255);
+    assert_eq!(round_corners(sample(), 0), sample());
+}
+
+#[test]
+fn temperature_trades_red_for_blue() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([100, 100, 100, 7])));
+    assert_eq!(temperature(img.clone(), 100).to_rgba8().get_pixel(0, 0).0, [120, 100, 80, 7]);
+    assert_eq!(temperature(img.clone(), -50).to_rgba8().get_pixel(0, 0).0, [90, 100, 110, 7]);
+    assert_eq!(temperature(img.clone(), 0), img);
+
+    let bright = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([250, 250, 250])));
+    assert_eq!(temperature(bright, 100).to_rgb8().get_pixel(0, 0).0, [255, 250, 200]);
+}
+
+#[test]
+fn keep_hue_grays_out_other_colors() {
+    let colors = [[220, 30, 30], [30, 200, 30], [30, 30, 220], [250, 40, 60]];
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 1, |x, _| Rgb(colors[x as usize])));
+    let kept = keep_hue(img.clone(), 0.0, 20.0).to_rgb8();
+    assert_eq!(kept.get_pixel(0, 0).0, colors[0]);
+    // 352 degrees is close to red going the other way around the wheel.
+    assert_eq!(kept.get_pixel(3, 0).0, colors[3]);
+    for x in 1..3 {
+        let Rgb([r, g, b]) = *kept.get_pixel(x, 0);
+        assert!(r == g && g == b, "pixel {} kept its color", x);
+    }
+    assert_eq!(keep_hue(img.clone(), 90.0, 360.0), img);
+}
+
+#[test]
+fn montage_pads_cells_with_background() {
+    let images = vec![sample(), sample(), sample()];
+    let img = montage(&images, 2, 1, [9, 9, 9], None).to_rgb8();
+    // Two columns and two rows of 5x3 cells with a 1 pixel border around each.
+    assert_eq!(img.dimensions(), (13, 9));
+    assert_eq!(img.get_pixel(0, 0).0, [9, 9, 9]);
+    assert_eq!(img.get_pixel(1, 5), sample().to_rgb8().get_pixel(0, 0));
+    assert_eq!(img.get_pixel(7, 5).0, [9, 9, 9]);
+}
+
+#[test]
+fn colorkey_clears_alpha_near_the_key() {
+    let img = colorkey(checkerboard(2, 4, 4, [0, 0, 0], [250, 250, 250]), 255, 255, 255, 10.0);
+    let img = img.as_rgba8().expect("colorkey should produce an rgba image");
+    assert_eq!(img.get_pixel(0, 0)[3], 255);
+    assert_eq!(img.get_pixel(2, 0)[3], 0);
+}
+
+#[test]
+fn rotate_arbitrary_by_90_matches_rotate() {
+    let img = rotate_arbitrary(sample(), 90.0, [0, 0, 0, 255], true);
+    assert_eq!(img, rotate(sample(), 90));
+}
+
+#[test]
+fn vignette_darkens_corners_but_not_the_center() {
+    let white = DynamicImage::ImageRgb8(RgbImage::from_pixel(9, 9, Rgb([255, 255, 255])));
+    assert_eq!(vignette(white.clone(), 0.0), white);
+    let img = vignette(white, 0.8).to_rgb8();
+    assert_eq!(img.get_pixel(4, 4).0, [255, 255, 255]);
+    assert!(img.get_pixel(0, 0)[0] < img.get_pixel(4, 0)[0]);
+}
+
+#[test]
+fn gamma_table_matches_powf() {
+    // Every channel value appears once, so the whole table is checked.
+    let ramp = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 1, |x, _| Rgb([x as u8, x as u8, x as u8])));
+    for g in [0.45, 2.2] {
+        let img = gamma(ramp.clone(), g).to_rgb8();
+        for (x, _, pixel) in img.enumerate_pixels() {
+            let expected = (255.0 * (x as f32 / 255.0).powf(1.0 / g)).round() as u8;
+            assert_eq!(pixel.0, [expected; 3]);
+        }
+    }
+}
+
+#[test]
+fn gamma_of_one_is_identity() {
+    assert_eq!(gamma(sample(), 1.0), sample());
+}
+
+#[test]
+fn scale_rounds_dimensions() {
+    assert_eq!(scale(sample(), 0.5).dimensions(), (3, 2));
+    assert_eq!(scale(sample(), 2.0).dimensions(), (10, 6));
+}
+
+#[test]
+fn fast_blur_is_close_to_gaussian_blur() {
+    // Three box passes of radius 3 have the variance of a Gaussian with
+    // sigma sqrt(12).
+    let img = checkerboard(8, 64, 64, [0, 0, 0], [255, 255, 255]);
+    let fast = fast_blur(img.clone(), 3).to_rgb8();
+    let gaussian = blur(img, 12f32.sqrt()).to_rgb8();
+    let total: u64 = fast.as_raw().iter().zip(gaussian.as_raw()).map(|(a, b)| a.abs_diff(*b) as u64).sum();
+    let mean = total as f64 / fast.as_raw().len() as f64;
+    assert!(mean < 8.0, "mean difference {}", mean);
+}
+
+#[test]
+fn median_removes_salt_and_pepper_noise() {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let mut noisy = RgbImage::from_pixel(32, 32, Rgb([128, 128, 128]));
+    for pixel in noisy.pixels_mut() {
+        if rng.gen_bool(0.1) {
+            *pixel = if rng.gen() { Rgb([255, 255, 255]) } else { Rgb([0, 0, 0]) };
+        }
+    }
+    let img = median(DynamicImage::ImageRgb8(noisy), 1).to_rgb8();
+    let flat = img.pixels().filter(|pixel| pixel.0 == [128, 128, 128]).count();
+    assert!(flat >= 32 * 32 * 99 / 100, "only {} flat pixels", flat);
+}
+
+#[test]
+fn oil_paint_averages_the_most_common_brightness() {
+    // Two similar grays share a level and average out, the lone bright
+    // speckle is painted over.
+    let img = RgbImage::from_fn(5, 5, |x, y| match (x, y) {
+        (2, 2) => Rgb([250, 250, 250]),
+        _ if x < 2 => Rgb([96, 96, 96]),
+        _ => Rgb([100, 100, 100]),
+    });
+    let painted = oil_paint(DynamicImage::ImageRgb8(img.clone()), 1, 4).to_rgb8();
+    assert_eq!(painted.get_pixel(2, 2).0, [99, 99, 99]);
+    assert_eq!(painted.get_pixel(4, 4).0, [100, 100, 100]);
+    assert_eq!(oil_paint(DynamicImage::ImageRgb8(img.clone()), 0, 4), DynamicImage::ImageRgb8(img));
+}
+
+#[test]
+fn pad_fills_by_mode() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(3, 1, |x, _| Rgb([x as u8 * 10; 3])));
+    let row = |mode| {
+        let padded = pad(img.clone(), 2, mode, [7, 8, 9]).to_rgb8();
+        assert_eq!(padded.dimensions(), (7, 5));
+        if mode != PadMode::Constant {
+            assert_eq!(padded.get_pixel(3, 0), padded.get_pixel(3, 2));
+        }
+        padded.rows().nth(2).unwrap().map(|pixel| pixel[0]).collect::<Vec<_>>()
+    };
+    assert_eq!(row(PadMode::Mirror), [20, 10, 0, 10, 20, 10, 0]);
+    assert_eq!(row(PadMode::Edge), [0, 0, 0, 10, 20, 20, 20]);
+    assert_eq!(row(PadMode::Constant), [7, 7, 0, 10, 20, 7, 7]);
+}
+
+#[test]
+fn trim_crops_to_the_content() {
+    let scan = RgbImage::from_fn(10, 8, |x, y| match (x, y) {
+        (3, 2) => Rgb([0, 0, 0]),
+        (6, 5) => Rgb([40, 40, 40]),
+        (0, _) => Rgb([250, 250, 250]),
+        _ => Rgb([255, 255, 255]),
+    });
+    let scan = DynamicImage::ImageRgb8(scan);
+    assert_eq!(trim_bounds(&scan, None, 10.0), Some((3, 2, 4, 4)));
+    assert_eq!(trim_bounds(&scan, Some([255, 255, 255]), 0.0), Some((0, 0, 7, 8)));
+    assert_eq!(trim(scan.clone(), None, 10.0).to_rgb8().get_pixel(3, 3).0, [40, 40, 40]);
+
+    let blank = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([9, 9, 9])));
+    assert_eq!(trim_bounds(&blank, None, 0.0), None);
+    assert_eq!(trim(blank.clone(), None, 0.0), blank);
+}
+
+#[test]
+fn dithering_tracks_a_gradient_closer_than_snapping() {
+    let gradient = DynamicImage::ImageLuma8(GrayImage::from_fn(256, 16, |x, _| Luma([x as u8])));
+    // How far the average of each 8 pixel wide strip is from the original.
+    let banding = |img: DynamicImage| {
+        let img = img.to_luma8();
+        let strip = |x0: u32| (x0..x0 + 8).flat_map(|x| (0..16).map(move |y| (x, y)));
+        let error: f32 = (0..256)
+            .step_by(8)
+            .map(|x0| {
+                let mean = strip(x0).map(|(x, y)| img.get_pixel(x, y)[0] as f32).sum::<f32>() / 128.0;
+                (mean - (x0 as f32 + 3.5)).abs()
+            })
+            .sum();
+        error / 32.0
+    };
+    let levels = Palette::Levels(4);
+    assert_eq!(dither(gradient.clone(), Dither::None, &levels), posterize(gradient.clone(), 4));
+    let flat = banding(dither(gradient.clone(), Dither::None, &levels));
+    let floyd = banding(dither(gradient.clone(), Dither::Floyd, &levels));
+    let ordered = banding(dither(gradient.clone(), Dither::Ordered, &levels));
+    assert!(flat > 15.0 && floyd < flat / 3.0 && ordered < flat / 3.0, "{} {} {}", flat, floyd, ordered);
+
+    let palette = Palette::Colors(quantize_palette(&gradient, 3));
+    let dithered = dither(gradient.clone(), Dither::Ordered, &palette);
+    assert_eq!(dithered, dither(gradient.clone(), Dither::Ordered, &palette));
+    // The darkest and brightest ends lie outside the palette either way.
+    let (dithered, snapped) = (banding(dithered), banding(quantize(gradient, 3)));
+    assert!(dithered < snapped * 0.75, "{} {}", dithered, snapped);
+}
+
+#[test]
+fn flatten_blends_over_the_background() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([200, 100, 0, 128])));
+    let img = flatten(img, [0, 0, 255]);
+    let img = img.as_rgb8().expect("flatten should produce an rgb image");
+    assert_eq!(img.get_pixel(0, 0).0, [100, 50, 127]);
+}
+
+#[test]
+fn swap_channels_reorders_and_keeps_alpha() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([1, 2, 3, 4])));
+    let img = swap_channels(img, [2, 0, 1]).to_rgba8();
+    assert_eq!(img.get_pixel(0, 0).0, [3, 1, 2, 4]);
+}
+
+#[test]
+fn pixelate_averages_partial_edge_blocks() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(5, 1, |x, _| Rgb([x as u8 * 10, 0, 0])));
+    let img = pixelate(img, 2, None).to_rgb8();
+    let reds: Vec<u8> = img.pixels().map(|pixel| pixel[0]).collect();
+    assert_eq!(reds, [5, 5, 25, 25, 40]);
+}
+
+#[test]
+fn pixelate_region_leaves_the_rest_unchanged() {
+    let img = pixelate(sample(), 2, Some((1, 1, 2, 2))).to_rgb8();
+    let original = sample().to_rgb8();
+    assert_eq!(img.get_pixel(0, 0), original.get_pixel(0, 0));
+    assert_eq!(img.get_pixel(3, 1), original.get_pixel(3, 1));
+    assert_eq!(img.get_pixel(1, 1), img.get_pixel(2, 2));
+}
+
+#[test]
+fn auto_contrast_stretches_to_the_full_range() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 1, |x, _| Rgb([100 + x as u8 * 10; 3])));
+    let img = auto_contrast(img, 0.0).to_rgb8();
+    let values: Vec<u8> = img.pixels().map(|pixel| pixel[0]).collect();
+    assert_eq!(values, [0, 85, 170, 255]);
+}
+
+#[test]
+fn auto_contrast_leaves_flat_images_unchanged() {
+    let gray = DynamicImage::ImageRgb8(RgbImage::from_pixel(3, 3, Rgb([128, 128, 128])));
+    assert_eq!(auto_contrast(gray.clone(), 1.0), gray);
+}
+
+#[test]
+fn thumbnail_shrinks_but_never_enlarges() {
+    assert_eq!(thumbnail(sample(), 100, 100), sample());
+    assert_eq!(thumbnail(checkerboard(4, 40, 20, [0, 0, 0], [255, 255, 255]), 10, 10).dimensions(), (10, 5));
+}
+
+#[test]
+fn sixteen_bit_images_keep_their_depth() {
+    let deep = DynamicImage::ImageRgb16(ImageBuffer::from_fn(3, 2, |x, y| Rgb([x as u16 * 1000 + 1, y as u16, 60000])));
+    let img = brighten(deep.clone(), 10);
+    let img = img.as_rgb16().expect("brighten should keep 16-bit samples");
+    assert_eq!(img.get_pixel(1, 1).0, [1001 + 2570, 1 + 2570, 62570]);
+    assert_eq!(invert(invert(deep.clone())), deep);
+    assert!(grayscale(deep).as_luma16().is_some());
+}
+
+#[test]
+fn bloom_leaves_black_unchanged_and_brightens_around_highlights() {
+    let black = DynamicImage::ImageRgb8(RgbImage::new(16, 16));
+    assert_eq!(bloom(black, 0, 2.0), DynamicImage::ImageRgb8(RgbImage::new(16, 16)));
+    let mut spot = RgbImage::new(16, 16);
+    spot.put_pixel(8, 8, Rgb([255, 255, 255]));
+    let img = bloom(DynamicImage::ImageRgb8(spot), 200, 1.0).to_rgb8();
+    assert!(img.get_pixel(9, 8)[0] > 0);
+    assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0]);
+}
+
+#[test]
+fn extract_channel_returns_one_channel_as_luma() {
+    let img = extract_channel(sample(), Channel::G);
+    let img = img.as_luma8().expect("extract_channel should produce a luma image");
+    assert_eq!(img.get_pixel(2, 1)[0], sample().to_rgb8().get_pixel(2, 1)[1]);
+    let alpha = extract_channel(sample(), Channel::A).to_luma8();
+    assert!(alpha.pixels().all(|pixel| pixel[0] == 255));
+}
+
+#[test]
+fn quantize_gradient_to_four_colors() {
+    let gradient = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 16, |x, y| Rgb([x as u8 * 4, y as u8 * 16, 128])));
+    let img = quantize(gradient, 4).to_rgb8();
+    let colors: HashSet<[u8; 3]> = img.pixels().map(|pixel| pixel.0).collect();
+    assert!(colors.len() <= 4, "{} colors", colors.len());
+}
+
+#[test]
+fn seam_carve_removes_the_flat_columns_first() {
+    // A bright column in the middle of a flat field is the only content.
+    let img = DynamicImage::ImageRgb8(RgbImage::from_fn(12, 6, |x, _| Rgb([if x == 6 { 255 } else { 0 }; 3])));
+    let carved = seam_carve(img, 8, 6).to_rgb8();
+    assert_eq!(carved.dimensions(), (8, 6));
+    let bright = carved.pixels().filter(|pixel| pixel.0 == [255, 255, 255]).count();
+    assert_eq!(bright, 6);
+}
+
+#[test]
+fn border_frames_the_image() {
+    assert_eq!(border(sample(), 0, [1, 2, 3]), sample());
+    let img = border(sample(), 2, [1, 2, 3]).to_rgb8();
+    assert_eq!(img.dimensions(), (9, 7));
+    assert_eq!(img.get_pixel(0, 0).0, [1, 2, 3]);
+    assert_eq!(img.get_pixel(8, 6).0, [1, 2, 3]);
+    assert_eq!(img.get_pixel(3, 3), sample().to_rgb8().get_pixel(1, 1));
+}
+
+#[test]
+fn crop_aspect_keeps_the_center() {
+    let img = checkerboard(1, 40, 10, [0, 0, 0], [255, 255, 255]);
+    let cropped = crop_aspect(img.clone(), 1, 1);
+    assert_eq!(cropped.dimensions(), (10, 10));
+    assert_eq!(cropped, img.crop_imm(15, 0, 10, 10));
+    assert_eq!(crop_aspect(img, 16, 9).dimensions(), (17, 10));
+}
+
+#[test]
+fn crop_pct_keeps_at_least_one_pixel() {
+    let img = checkerboard(1, 40, 10, [0, 0, 0], [255, 255, 255]);
+    assert_eq!(crop_pct(img.clone(), 25.0, 0.0, 50.0, 100.0).dimensions(), (20, 10));
+    assert_eq!(crop_pct(img, 50.0, 50.0, 0.01, 0.01).dimensions(), (1, 1));
+}
+
+#[test]
+fn difference_of_gaussians_is_black_on_flat_images() {
+    let flat = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([90, 90, 90])));
+    let img = difference_of_gaussians(flat, 1.0, 3.0, true);
+    let img = img.as_luma8().expect("dog should produce a luma image");
+    assert!(img.pixels().all(|pixel| pixel[0] == 0));
+    let normalized = difference_of_gaussians(checkerboard(4, 16, 16, [0, 0, 0], [255, 255, 255]), 1.0, 3.0, true);
+    assert_eq!(normalized.to_luma8().pixels().map(|pixel| pixel[0]).max(), Some(255));
+}